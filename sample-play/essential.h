@@ -24,6 +24,79 @@
     }\
 } while (0)
 
+// typed checks defined by runtime.h, they send the expected and
+// actual values to the runner and return 0 when the check failed
+int assertion_eq_int(const char* func, const char* file, int line, long long expected, long long actual);
+int assertion_ne_int(const char* func, const char* file, int line, long long expected, long long actual);
+int assertion_eq_str(const char* func, const char* file, int line, const char* expected, const char* actual);
+int assertion_ne_str(const char* func, const char* file, int line, const char* expected, const char* actual);
+int assertion_near_double(const char* func, const char* file, int line, double expected, double actual, double tolerance);
+int assertion_mem_eq(const char* func, const char* file, int line, const void* expected, const void* actual, size_t size);
+int assertion_null(const char* func, const char* file, int line, const void* actual);
+int assertion_not_null(const char* func, const char* file, int line, const void* actual);
+
+#define ASSERTION(check) do{\
+    if(!(check)){\
+        char* fail_msg = malloc(MESSAGE_BUFFER); \
+        snprintf(fail_msg, MESSAGE_BUFFER, "Assertion failed: %s:%d", __FILE__, __LINE__); \
+        pthread_exit((void*)fail_msg);\
+    }\
+} while (0)
+
+#define assert_eq_int(expected, actual) \
+    ASSERTION(assertion_eq_int(__func__, __FILE__, __LINE__, (expected), (actual)))
+#define assert_ne_int(expected, actual) \
+    ASSERTION(assertion_ne_int(__func__, __FILE__, __LINE__, (expected), (actual)))
+#define assert_eq_str(expected, actual) \
+    ASSERTION(assertion_eq_str(__func__, __FILE__, __LINE__, (expected), (actual)))
+#define assert_ne_str(expected, actual) \
+    ASSERTION(assertion_ne_str(__func__, __FILE__, __LINE__, (expected), (actual)))
+#define assert_near_double(expected, actual, tolerance) \
+    ASSERTION(assertion_near_double(__func__, __FILE__, __LINE__, (expected), (actual), (tolerance)))
+#define assert_mem_eq(expected, actual, size) \
+    ASSERTION(assertion_mem_eq(__func__, __FILE__, __LINE__, (expected), (actual), (size)))
+#define assert_null(actual) \
+    ASSERTION(assertion_null(__func__, __FILE__, __LINE__, (actual)))
+#define assert_not_null(actual) \
+    ASSERTION(assertion_not_null(__func__, __FILE__, __LINE__, (actual)))
+
+// records a failed expectation of the running test, typed checks
+// already sent their values and only count the failure
+void expectation_failed(const char* file, int line);
+void typed_expectation_failed(void);
+
+// non-fatal counterpart of ASSERTION, the test keeps
+// running and fails once it returns
+#define EXPECTATION(check) do{\
+    if(!(check)){\
+        expectation_failed(__FILE__, __LINE__);\
+    }\
+} while (0)
+
+#define TYPED_EXPECTATION(check) do{\
+    if(!(check)){\
+        typed_expectation_failed();\
+    }\
+} while (0)
+
+#define expect(condition) EXPECTATION(condition)
+#define expect_eq_int(expected, actual) \
+    TYPED_EXPECTATION(assertion_eq_int(__func__, __FILE__, __LINE__, (expected), (actual)))
+#define expect_ne_int(expected, actual) \
+    TYPED_EXPECTATION(assertion_ne_int(__func__, __FILE__, __LINE__, (expected), (actual)))
+#define expect_eq_str(expected, actual) \
+    TYPED_EXPECTATION(assertion_eq_str(__func__, __FILE__, __LINE__, (expected), (actual)))
+#define expect_ne_str(expected, actual) \
+    TYPED_EXPECTATION(assertion_ne_str(__func__, __FILE__, __LINE__, (expected), (actual)))
+#define expect_near_double(expected, actual, tolerance) \
+    TYPED_EXPECTATION(assertion_near_double(__func__, __FILE__, __LINE__, (expected), (actual), (tolerance)))
+#define expect_mem_eq(expected, actual, size) \
+    TYPED_EXPECTATION(assertion_mem_eq(__func__, __FILE__, __LINE__, (expected), (actual), (size)))
+#define expect_null(actual) \
+    TYPED_EXPECTATION(assertion_null(__func__, __FILE__, __LINE__, (actual)))
+#define expect_not_null(actual) \
+    TYPED_EXPECTATION(assertion_not_null(__func__, __FILE__, __LINE__, (actual)))
+
 #define debug(fmt, ...) do{\
     ProcessData data = {\
         .info_type = Log,\
@@ -42,12 +115,16 @@
         &data,\
         1,\
         sizeof(ProcessData),\
-        stdout\
+        RUNNER_PIPE\
     );\
 } while(0);
 
 
 #define TEST(title) void* title(void*)
 
+// setup and teardown hooks, named by defining SUITE_SETUP,
+// SUITE_TEARDOWN, TEST_SETUP or TEST_TEARDOWN next to TEST_CASES
+#define FIXTURE(title) void* title(void*)
+
 
 #endif
\ No newline at end of file
@@ -5,35 +5,118 @@
 
 #ifndef INCLUDES_FOR_TEST_RUNTIME
 #define INCLUDES_FOR_TEST_RUNTIME
+#ifndef _GNU_SOURCE
+#define _GNU_SOURCE
+#endif
 #include <stdio.h>
 #include "essential.h"
 #include "support.h"
 #include <pthread.h>
 #include <errno.h>
+#include <stdlib.h>
+#include <string.h>
+#include <fnmatch.h>
+#include <regex.h>
+#include <stdint.h>
+#include <time.h>
+#include <signal.h>
+#include <unistd.h>
+#include <execinfo.h>
+#include <fcntl.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
 #endif
 
 
 
 
 const char *PROGRAM_NAME = NULL;
+FILE *RUNNER_PIPE = NULL;
+
 
 
 
 
 
 
+// serial tests are listed like any other test,
+// except when collecting which tests are serial
+#define SERIAL_TEST_CASE(func, ssize) TEST_CASE(func, ssize)
 
 #define TEST_CASE(func, _ssize) void* func(void*);
 TEST_CASES
 #undef TEST_CASE
 
+#define FIXTURE_STR_(name) #name
+#define FIXTURE_STR(name) FIXTURE_STR_(name)
+
+struct fixture {
+    void* (*func)(void*);
+    const char *name;
+};
+
+// suite fixtures run once around all tests,
+// test fixtures around every test on the test's own thread
+#ifdef SUITE_SETUP
+void* SUITE_SETUP(void*);
+static const struct fixture suite_setup = { SUITE_SETUP, FIXTURE_STR(SUITE_SETUP) };
+#else
+static const struct fixture suite_setup = { NULL, NULL };
+#endif
+
+#ifdef SUITE_TEARDOWN
+void* SUITE_TEARDOWN(void*);
+static const struct fixture suite_teardown = { SUITE_TEARDOWN, FIXTURE_STR(SUITE_TEARDOWN) };
+#else
+static const struct fixture suite_teardown = { NULL, NULL };
+#endif
+
+#ifdef TEST_SETUP
+void* TEST_SETUP(void*);
+static const struct fixture test_setup = { TEST_SETUP, FIXTURE_STR(TEST_SETUP) };
+#else
+static const struct fixture test_setup = { NULL, NULL };
+#endif
+
+#ifdef TEST_TEARDOWN
+void* TEST_TEARDOWN(void*);
+static const struct fixture test_teardown = { TEST_TEARDOWN, FIXTURE_STR(TEST_TEARDOWN) };
+#else
+static const struct fixture test_teardown = { NULL, NULL };
+#endif
+
 struct test_case{
     pthread_t tr;
+    int running;
+    struct timespec started;
     size_t ssize;
     char *thread_name;
     void* (*func_ptr)(void*);
+    // declared with SERIAL_TEST_CASE
+    int serial;
+    int selected;
+    // name of the fixture that failed instead of the test
+    const char *failed_fixture;
+    // expect_* checks that failed, only touched by the test's threads
+    int failed_expectations;
+    // stack the crash handler runs on, survives a test overflowing its own
+    void *crash_stack;
+    // process running the test in isolation mode
+    pid_t pid;
+    // shared with the forked process, set once the test's status is sent
+    volatile int *reported;
 };
 
+#define CRASH_STACK_SIZE (64 * 1024)
+#define CRASH_BACKTRACE_DEPTH 16
+
+// test run by the current thread, read by the crash handler
+static __thread struct test_case *CURRENT_TEST = NULL;
+// tests of the process, the crash handler reports the ones
+// still running when another test crashes
+static struct test_case *RUNNING_TESTS = NULL;
+static size_t RUNNING_TESTS_LEN = 0;
+
 struct contents {
     struct test_case *c;
     size_t len;
@@ -61,11 +144,23 @@ struct contents init_thread_contents(){
         #undef TEST_CASE
     };
 
+    // 1 for tests declared with SERIAL_TEST_CASE
+    int serials[] = {
+        #undef SERIAL_TEST_CASE
+        #define SERIAL_TEST_CASE(_func, _ssize) 1 ,
+        #define TEST_CASE(_func, _ssize) 0 ,
+        TEST_CASES
+        #undef TEST_CASE
+        #undef SERIAL_TEST_CASE
+        #define SERIAL_TEST_CASE(func, ssize) TEST_CASE(func, ssize)
+    };
+
     struct test_case *test_list = malloc(sizeof(struct test_case) * len);
     for (size_t i = 0; i < len; i++){
         test_list[i].func_ptr = func_ptrs[i];
         test_list[i].thread_name = function_names[i];
         test_list[i].ssize = ssizes[i]; 
+        test_list[i].serial = serials[i];
     }
     
     return (struct contents){
@@ -86,12 +181,26 @@ void send_warning_msg(
 void send_status(
     const char* program_name, 
     char* from_test, 
-    enum StatusType t
+    enum StatusType t,
+    uint64_t duration_ns
 );
+void send_hello(const char* program_name);
 void send_register(
     const char* program_name, 
     char* test_name
 );
+int test_selected(const char* test_name);
+uint64_t elapsed_ns(const struct timespec* since);
+void* run_test(void* test);
+char* run_in_thread(struct test_case* test, void* (*func)(void*), size_t ssize);
+void report_fixture_failure(const char* test_name, const char* fixture, char* catch, uint64_t duration);
+void install_crash_handler(void);
+static void open_backtrace_pipe(void);
+void report_result(struct test_case* test, char* catch);
+int launch_test(struct test_case* test, pthread_attr_t* attr, int isolate);
+void wait_tests(struct test_case* thread_list, size_t len, int isolate, size_t waiting);
+pid_t fork_test(struct test_case* test, pthread_attr_t* attr);
+void report_lost_test(struct test_case* test, int wstatus);
 
 int main(int argc, char const *argv[]){
 
@@ -99,6 +208,24 @@ int main(int argc, char const *argv[]){
     if (argc > 0) {
         PROGRAM_NAME = argv[0];  
     }
+    if (getenv(PROGRAM_ENV) != NULL) {
+        PROGRAM_NAME = getenv(PROGRAM_ENV);
+    }
+
+    // frames go to the descriptor given by the runner,
+    // stdout when the binary is executed by hand
+    RUNNER_PIPE = stdout;
+    const char *protocol_fd = getenv(PROTOCOL_FD_ENV);
+    if (protocol_fd != NULL) {
+        FILE *pipe = fdopen(atoi(protocol_fd), "w");
+        if (pipe != NULL) {
+            RUNNER_PIPE = pipe;
+        }
+    }
+    // every frame is written at once, so frames sent before
+    // a sanitizer or a crash ends the process still reach the runner
+    setvbuf(RUNNER_PIPE, NULL, _IONBF, 0);
+    send_hello(PROGRAM_NAME);
 
     // const char const *assigned_key = argv[0];
     size_t len; 
@@ -110,38 +237,237 @@ int main(int argc, char const *argv[]){
         thread_list = c.c;
     }
     
+    // only register tests when the runner is listing them
+    int list_only = getenv(LIST_ENV) != NULL;
+    if (!list_only) {
+        install_crash_handler();
+    }
+
+    // every test forks its own process, a crash only takes down that test
+    int isolate = !list_only && getenv(ISOLATE_ENV) != NULL;
+    // set once a test's status is sent, shared with the forked processes
+    int *reported = NULL;
+    if (isolate) {
+        reported = mmap(
+            NULL, 
+            sizeof(int) * len, 
+            PROT_READ | PROT_WRITE, 
+            MAP_SHARED | MAP_ANONYMOUS, 
+            -1, 
+            0
+        );
+        if (reported == MAP_FAILED) {
+            perror("Unable to map isolation flags, running tests as threads");
+            reported = NULL;
+            isolate = 0;
+        }
+    }
+    if (!isolate && !list_only) {
+        reported = calloc(len, sizeof(int));
+    }
+    RUNNING_TESTS = thread_list;
+    RUNNING_TESTS_LEN = len;
+
+    // a failing suite setup fails every test without running them
+    char *suite_failure = NULL;
+    if (!list_only && suite_setup.func != NULL) {
+        suite_failure = run_in_thread(NULL, suite_setup.func, DEFAULT_SSIZE);
+    }
+
+    // tests marked with SERIAL_TEST_CASE, or all of them when the
+    // runner asks for it, run alone after the concurrent ones
+    int serial_all = getenv(SERIAL_ENV) != NULL;
+
+    pthread_attr_t attr;
+    pthread_attr_init(&attr);
+
     // init threads
-    {
-        pthread_attr_t attr;
-        pthread_attr_init(&attr);
-        // char **results = calloc(len, sizeof(char*));
+    size_t waiting = 0;
+    for (size_t i = 0; i < len; i++){
+        thread_list[i].running = 0;
+        thread_list[i].selected = 0;
+        thread_list[i].failed_fixture = NULL;
+        thread_list[i].failed_expectations = 0;
+        thread_list[i].crash_stack = NULL;
+        thread_list[i].pid = 0;
+        thread_list[i].reported = reported != NULL ? &reported[i] : NULL;
+
+        if(thread_list[i].func_ptr == NULL){
+            continue;
+        }
+
+        // tests filtered out by the runner are never registered
+        if(!test_selected(thread_list[i].thread_name)){
+            continue;
+        }
+        
+        // send information of test case 
+        // to parent test runner process 
+        send_register(PROGRAM_NAME, thread_list[i].thread_name);
+
+        if(list_only){
+            continue;
+        }
+
+        if(suite_failure != NULL){
+            report_fixture_failure(
+                thread_list[i].thread_name, 
+                suite_setup.name, 
+                strdup(suite_failure), 
+                0
+            );
+            continue;
+        }
+
+        thread_list[i].selected = 1;
+        if(serial_all || thread_list[i].serial){
+            continue;
+        }
+
+        waiting += launch_test(&thread_list[i], &attr, isolate);
+    }
+    
+    // wait for results
+    wait_tests(thread_list, len, isolate, waiting);
+
+    for (size_t i = 0; i < len; i++){
+        if(!thread_list[i].selected || !(serial_all || thread_list[i].serial)){
+            continue;
+        }
+
+        wait_tests(thread_list, len, isolate, launch_test(&thread_list[i], &attr, isolate));
+    }
+    pthread_attr_destroy(&attr);
+
+    // reported as a test of its own, every other test already ended
+    if (!list_only && suite_failure == NULL && suite_teardown.func != NULL) {
+        char *catch = run_in_thread(NULL, suite_teardown.func, DEFAULT_SSIZE);
+        if (catch != NULL) {
+            send_register(PROGRAM_NAME, (char*)suite_teardown.name);
+            report_fixture_failure(suite_teardown.name, suite_teardown.name, catch, 0);
+        }
+    }
+    free(suite_failure);
+
+    for (size_t i = 0; i < len; i++){
+        free(thread_list[i].crash_stack);
+    }
+    RUNNING_TESTS_LEN = 0;
+    if (isolate) {
+        munmap(reported, sizeof(int) * len);
+    } else {
+        free(reported);
+    }
+    free(thread_list);
+    fflush(RUNNER_PIPE);
+
+    return 0;
+}
+
+
+
+
+// returns 1 when `name` matches one of the newline separated patterns,
+// patterns starting with "re:" are extended regular expressions
+static int matches_any_pattern(const char* patterns, const char* name){
+    char *list = strdup(patterns);
+    char *save = NULL;
+    int found = 0;
+
+    for (
+        char *p = strtok_r(list, "\n", &save); 
+        p != NULL && !found; 
+        p = strtok_r(NULL, "\n", &save)
+    ){
+        if (strncmp(p, REGEX_PATTERN_PREFIX, strlen(REGEX_PATTERN_PREFIX)) == 0) {
+            regex_t re;
+            if (regcomp(&re, p + strlen(REGEX_PATTERN_PREFIX), REG_EXTENDED | REG_NOSUB) == 0) {
+                found = regexec(&re, name, 0, NULL, 0) == 0;
+                regfree(&re);
+            }
+        } else {
+            found = fnmatch(p, name, 0) == 0;
+        }
+    }
+
+    free(list);
+    return found;
+}
+
+int test_selected(const char* test_name){
+    const char *include = getenv(TEST_FILTER_ENV);
+    const char *exclude = getenv(TEST_EXCLUDE_ENV);
+
+    if (include != NULL && !matches_any_pattern(include, test_name)) {
+        return 0;
+    }
+    if (exclude != NULL && matches_any_pattern(exclude, test_name)) {
+        return 0;
+    }
+
+    return 1;
+}
+
+// starts a test on a thread, or a forked process in isolation
+// mode, returns 1 when it is running
+int launch_test(struct test_case* test, pthread_attr_t* attr, int isolate){
+    pthread_attr_setstacksize(attr, test->ssize);
+
+    if(isolate){
+        *test->reported = 0;
+        test->pid = fork_test(test, attr);
+        test->running = test->pid > 0;
+        return test->running;
+    }
+
+    test->crash_stack = malloc(CRASH_STACK_SIZE);
+    clock_gettime(CLOCK_MONOTONIC, &test->started);
+    pthread_create(
+        &test->tr,
+        attr,
+        run_test,
+        test
+    );
+    test->running = 1;
+    return 1;
+}
+
+// waits until the `waiting` running tests ended and sends their results
+void wait_tests(struct test_case* thread_list, size_t len, int isolate, size_t waiting){
+    // wait for forked tests, the ones that did not report
+    // before their process ended are reported here
+    while (isolate && waiting){
+        int wstatus;
+        pid_t pid = waitpid(-1, &wstatus, 0);
+        if (pid < 0) {
+            if (errno == EINTR) {
+                continue;
+            }
+            perror("waitpid error");
+            return;
+        }
+
         for (size_t i = 0; i < len; i++){
-            if(thread_list[i].func_ptr == NULL){
+            if(!thread_list[i].running || thread_list[i].pid != pid){
                 continue;
             }
-            
-            // send information of test case 
-            // to parent test runner process 
-            send_register(argv[0], thread_list[i].thread_name);
 
-            pthread_attr_setstacksize(&attr, thread_list[i].ssize);
-            
-            pthread_create(
-                &thread_list[i].tr,
-                &attr,
-                thread_list[i].func_ptr,
-                NULL
-            );
+            if(!*thread_list[i].reported){
+                report_lost_test(&thread_list[i], wstatus);
+            }
+            thread_list[i].running = 0;
+            waiting--;
         }
-        pthread_attr_destroy(&attr);
     }
-    
+
     // wait for results
-    size_t waiting = len;
     char *catch;
     while (waiting){
         // Waiting for test case...
         for (size_t i = 0; i < len; i++){
+            if(!thread_list[i].running){
+                continue;
+            }
 
             int res = pthread_tryjoin_np(
                 thread_list[i].tr,
@@ -149,21 +475,9 @@ int main(int argc, char const *argv[]){
             );
 
             if (res == 0) {
-
-                if(catch){
-                    // encounters an error
-                    send_status(argv[0], thread_list[i].thread_name, Fail);
-                    send_warning_msg(argv[0], thread_list[i].thread_name,(const char*)catch);
-                    free(catch);
-                } else {
-
-                    // test successfully ended 
-                    send_status(argv[0], thread_list[i].thread_name, Success);
-                    
-                }
-
+                report_result(&thread_list[i], catch);
+                thread_list[i].running = 0;
                 waiting--;
-                break;
             } else if (res == EBUSY) {
 
                 // printf("Thread still working...\n");
@@ -172,28 +486,413 @@ int main(int argc, char const *argv[]){
 
                 // TODO 
                 perror("%s: pthread_tryjoin_np error - TODO!!!");
-                break;
+                thread_list[i].running = 0;
+                waiting--;
             }
             
         }
         
     }
+}
 
-    free(thread_list);
+// sends the status of a test that returned
+void report_result(struct test_case* test, char* catch){
+    uint64_t duration = elapsed_ns(&test->started);
+
+    if(catch && test->failed_fixture){
+        report_fixture_failure(test->thread_name, test->failed_fixture, catch, duration);
+    } else if(!catch && test->failed_expectations > 0){
+        char msg[MESSAGE_BUFFER];
+        snprintf(msg, MESSAGE_BUFFER, "%d expectations failed", test->failed_expectations);
+        send_status(PROGRAM_NAME, test->thread_name, Fail, duration);
+        send_warning_msg(PROGRAM_NAME, test->thread_name, msg);
+    } else if(catch){
+        // encounters an error
+        send_status(PROGRAM_NAME, test->thread_name, Fail, duration);
+        send_warning_msg(PROGRAM_NAME, test->thread_name, (const char*)catch);
+        free(catch);
+    } else {
+
+        // test successfully ended 
+        send_status(PROGRAM_NAME, test->thread_name, Success, duration);
+    }
 
-    return 0;
+    if (test->reported != NULL) {
+        *test->reported = 1;
+    }
 }
 
+// runs the test on a thread of a forked process, so it keeps its
+// requested stack size, returns the child's pid or -1
+pid_t fork_test(struct test_case* test, pthread_attr_t* attr){
+    clock_gettime(CLOCK_MONOTONIC, &test->started);
+
+    // output still buffered, e.g. printed by the suite setup, would
+    // be copied into the child and written again when it exits
+    fflush(NULL);
+    pid_t pid = fork();
+    if (pid < 0) {
+        char msg[MESSAGE_BUFFER];
+        snprintf(msg, MESSAGE_BUFFER, "Unable to fork test: %s", strerror(errno));
+        send_status(PROGRAM_NAME, test->thread_name, Fail, 0);
+        send_warning_msg(PROGRAM_NAME, test->thread_name, msg);
+        return -1;
+    }
+    if (pid > 0) {
+        return pid;
+    }
+
+    // the other tests run in processes of their own
+    RUNNING_TESTS_LEN = 0;
+    test->crash_stack = malloc(CRASH_STACK_SIZE);
+    open_backtrace_pipe();
+    char *catch = NULL;
+    if (pthread_create(&test->tr, attr, run_test, test) == 0) {
+        pthread_join(test->tr, (void**)&catch);
+        report_result(test, catch);
+    }
+    free(test->crash_stack);
+
+    // exit handlers still run, e.g. to write coverage data, and
+    // flush only what the test printed
+    exit(0);
+}
 
+// the forked process ended without sending a status,
+// e.g. killed by a signal the crash handler can't catch
+void report_lost_test(struct test_case* test, int wstatus){
+    uint64_t duration = elapsed_ns(&test->started);
+    char msg[MESSAGE_BUFFER];
+
+    if (WIFSIGNALED(wstatus)) {
+        int sig = WTERMSIG(wstatus);
+        snprintf(msg, MESSAGE_BUFFER, "Killed by signal %d (%s)", sig, strsignal(sig));
+        send_status(PROGRAM_NAME, test->thread_name, Crash, duration);
+    } else {
+        snprintf(msg, MESSAGE_BUFFER, "Exited with code %d", WEXITSTATUS(wstatus));
+        send_status(PROGRAM_NAME, test->thread_name, Fail, duration);
+    }
+    send_warning_msg(PROGRAM_NAME, test->thread_name, msg);
+}
+
+void report_fixture_failure(const char* test_name, const char* fixture, char* catch, uint64_t duration){
+    char msg[MESSAGE_BUFFER];
+    snprintf(msg, MESSAGE_BUFFER, "Fixture %s failed", fixture);
+
+    send_status(PROGRAM_NAME, (char*)test_name, SetupFail, duration);
+    send_warning_msg(PROGRAM_NAME, test_name, msg);
+    send_warning_msg(PROGRAM_NAME, test_name, catch);
+    free(catch);
+}
+
+struct stage {
+    struct test_case *test;
+    void* (*func)(void*);
+};
+
+// runs a test or fixture on its thread, remembering which test
+// the thread belongs to in case it crashes
+static void* run_stage(void* arg){
+    struct stage *stage = arg;
+    struct test_case *test = stage->test;
+    CURRENT_TEST = test;
+
+    // a sanitizer gives every thread a stack of its own and frees it
+    // when the thread ends, it is kept
+    stack_t current;
+    int has_stack = sigaltstack(NULL, &current) == 0 && !(current.ss_flags & SS_DISABLE);
+    if (test != NULL && test->crash_stack != NULL && !has_stack) {
+        stack_t stack = {
+            .ss_sp = test->crash_stack,
+            .ss_size = CRASH_STACK_SIZE,
+            .ss_flags = 0
+        };
+        sigaltstack(&stack, NULL);
+    }
+
+    return stage->func(NULL);
+}
+
+// fixtures end through the same pthread_exit based macros as tests, so
+// they get a thread of their own, returns the failure message or NULL
+char* run_in_thread(struct test_case* test, void* (*func)(void*), size_t ssize){
+    struct stage stage = { .test = test, .func = func };
+    pthread_attr_t attr;
+    pthread_attr_init(&attr);
+    pthread_attr_setstacksize(&attr, ssize);
+
+    pthread_t tr;
+    char *catch = NULL;
+    if (pthread_create(&tr, &attr, run_stage, &stage) == 0) {
+        pthread_join(tr, (void**)&catch);
+    } else {
+        catch = strdup("Unable to start thread");
+    }
+
+    pthread_attr_destroy(&attr);
+    return catch;
+}
+
+// runs a test with its setup and teardown fixtures, a failing
+// teardown only fails a test that passed
+void* run_test(void* arg){
+    struct test_case *test = arg;
+    struct stage stage = { .test = test, .func = test->func_ptr };
+
+    if (test_setup.func == NULL && test_teardown.func == NULL) {
+        return run_stage(&stage);
+    }
+
+    char *catch = NULL;
+    if (test_setup.func != NULL) {
+        catch = run_in_thread(test, test_setup.func, DEFAULT_SSIZE);
+        if (catch != NULL) {
+            test->failed_fixture = test_setup.name;
+            return catch;
+        }
+    }
+
+    catch = run_in_thread(test, test->func_ptr, test->ssize);
+
+    if (test_teardown.func != NULL) {
+        char *teardown = run_in_thread(test, test_teardown.func, DEFAULT_SSIZE);
+        if (teardown != NULL && catch == NULL) {
+            test->failed_fixture = test_teardown.name;
+            catch = teardown;
+        } else {
+            free(teardown);
+        }
+    }
+
+    return catch;
+}
 
+// frames are written straight to the descriptor, the crash
+// may have happened while stdio held its lock
+static void write_frame(const ProcessData* data){
+    const char *bytes = (const char*)data;
+    size_t left = sizeof(ProcessData);
 
-void send_status(const char* program_name, char* from_test, enum StatusType t) {
+    while (left > 0) {
+        ssize_t written = write(fileno(RUNNER_PIPE), bytes, left);
+        if (written <= 0) {
+            return;
+        }
+        bytes += written;
+        left -= (size_t)written;
+    }
+}
+
+static void send_crash_log(const char* test_name, enum LogType t, const char* msg){
+    ProcessData data;
+    memset(&data, 0, sizeof(ProcessData));
+    data.info_type = Log;
+    data.log.t = t;
+
+    strncpy(data.log.program_name, PROGRAM_NAME, PROGRAM_NAME_MAX_CHAR_SIZE - 1);
+    strncpy(data.log.function_name, test_name, FUNCTION_MAX_CHAR_SIZE - 1);
+    strncpy(data.log.msg, msg, MESSAGE_BUFFER - 1);
+
+    write_frame(&data);
+}
+
+// handlers found at install, e.g. a sanitizer's, called after the report
+static struct sigaction PREVIOUS_HANDLERS[NSIG];
+// backtrace_symbols_fd writes the frames here to be read back as logs
+static int BACKTRACE_PIPE[2] = { -1, -1 };
+// the handler can't format with stdio, messages are built by hand
+static char CRASH_MSG[MESSAGE_BUFFER];
+static char CRASH_FRAMES[MESSAGE_BUFFER * CRASH_BACKTRACE_DEPTH];
+
+static size_t append_str(char* buf, size_t at, const char* s){
+    while (*s != '\0' && at < MESSAGE_BUFFER - 1) {
+        buf[at++] = *s++;
+    }
+    buf[at] = '\0';
+    return at;
+}
+
+static size_t append_int(char* buf, size_t at, int n){
+    char digits[16];
+    size_t len = 0;
+    unsigned int u = n < 0 ? 0u - (unsigned int)n : (unsigned int)n;
+
+    do {
+        digits[len++] = (char)('0' + u % 10);
+        u /= 10;
+    } while (u > 0);
+    if (n < 0) {
+        at = append_str(buf, at, "-");
+    }
+    while (len > 0 && at < MESSAGE_BUFFER - 1) {
+        buf[at++] = digits[--len];
+    }
+    buf[at] = '\0';
+    return at;
+}
+
+static const char* signal_name(int sig){
+    switch (sig) {
+        case SIGSEGV: return "SIGSEGV";
+        case SIGABRT: return "SIGABRT";
+        case SIGFPE: return "SIGFPE";
+        case SIGBUS: return "SIGBUS";
+        case SIGILL: return "SIGILL";
+        default: return "unknown";
+    }
+}
+
+// the pipe is per process, so forked tests don't read each other's frames
+static void open_backtrace_pipe(void){
+    if (BACKTRACE_PIPE[0] >= 0) {
+        close(BACKTRACE_PIPE[0]);
+        close(BACKTRACE_PIPE[1]);
+    }
+    if (pipe2(BACKTRACE_PIPE, O_CLOEXEC | O_NONBLOCK) != 0) {
+        BACKTRACE_PIPE[0] = BACKTRACE_PIPE[1] = -1;
+    }
+}
+
+// sends the frames of the crashing thread, one log per line
+static void send_crash_backtrace(const char* test_name, void** frames, int depth){
+    if (BACKTRACE_PIPE[1] < 0 || depth <= 0) {
+        return;
+    }
+    backtrace_symbols_fd(frames, depth, BACKTRACE_PIPE[1]);
+
+    ssize_t len = read(BACKTRACE_PIPE[0], CRASH_FRAMES, sizeof(CRASH_FRAMES) - 1);
+    if (len <= 0) {
+        return;
+    }
+    CRASH_FRAMES[len] = '\0';
+
+    int frame = 0;
+    char *line = CRASH_FRAMES;
+    while (*line != '\0') {
+        char *end = line;
+        while (*end != '\0' && *end != '\n') {
+            end++;
+        }
+        int last = *end == '\0';
+        *end = '\0';
+
+        size_t at = append_str(CRASH_MSG, 0, "#");
+        at = append_int(CRASH_MSG, at, frame++);
+        at = append_str(CRASH_MSG, at, " ");
+        append_str(CRASH_MSG, at, line);
+        send_crash_log(test_name, Backtrace, CRASH_MSG);
+
+        if (last) {
+            break;
+        }
+        line = end + 1;
+    }
+}
+
+static void send_crash_status(struct test_case* test, enum StatusType t){
+    ProcessData data;
+    memset(&data, 0, sizeof(ProcessData));
+    data.info_type = Status;
+    data.stat.t = t;
+    data.stat.duration_ns = elapsed_ns(&test->started);
+    strncpy(data.stat.program_name, PROGRAM_NAME, PROGRAM_NAME_MAX_CHAR_SIZE - 1);
+    strncpy(data.stat.function_name, test->thread_name, FUNCTION_MAX_CHAR_SIZE - 1);
+    write_frame(&data);
+
+    if (test->reported != NULL) {
+        *test->reported = 1;
+    }
+}
+
+// best effort report of the crashing test, only async-signal-safe calls
+// are made, then the handler found at install, e.g. a sanitizer's, runs
+// or the default action is restored and the signal raised again
+static void crash_handler(int sig, siginfo_t* info, void* context){
+    struct test_case *test = CURRENT_TEST;
+
+    if (test != NULL) {
+        send_crash_status(test, Crash);
+
+        size_t at = append_str(CRASH_MSG, 0, "Crashed with signal ");
+        at = append_int(CRASH_MSG, at, sig);
+        at = append_str(CRASH_MSG, at, " (");
+        at = append_str(CRASH_MSG, at, signal_name(sig));
+        append_str(CRASH_MSG, at, ")");
+        send_crash_log(test->thread_name, Warning, CRASH_MSG);
+
+        // the handler and the signal trampoline are left out
+        void *frames[CRASH_BACKTRACE_DEPTH];
+        int depth = backtrace(frames, CRASH_BACKTRACE_DEPTH);
+        send_crash_backtrace(test->thread_name, frames + 2, depth - 2);
+
+        // tests running next to it end with the process without being blamed
+        for (size_t i = 0; i < RUNNING_TESTS_LEN; i++) {
+            struct test_case *other = &RUNNING_TESTS[i];
+            if (other == test || !other->running || other->reported == NULL || *other->reported) {
+                continue;
+            }
+
+            send_crash_status(other, Interrupted);
+            at = append_str(CRASH_MSG, 0, "Did not finish: process crashed in ");
+            append_str(CRASH_MSG, at, test->thread_name);
+            send_crash_log(other->thread_name, Warning, CRASH_MSG);
+        }
+    }
+
+    struct sigaction *previous = &PREVIOUS_HANDLERS[sig];
+    if (previous->sa_flags & SA_SIGINFO) {
+        previous->sa_sigaction(sig, info, context);
+        return;
+    }
+    if (previous->sa_handler != SIG_DFL && previous->sa_handler != SIG_IGN) {
+        previous->sa_handler(sig);
+        return;
+    }
+
+    signal(sig, SIG_DFL);
+    raise(sig);
+}
+
+void install_crash_handler(void){
+    // backtrace loads libgcc on its first call, which
+    // can't be done safely from inside the handler
+    void *warm_up[1];
+    backtrace(warm_up, 1);
+    open_backtrace_pipe();
+
+    struct sigaction action;
+    memset(&action, 0, sizeof(action));
+    action.sa_sigaction = crash_handler;
+    action.sa_flags = SA_SIGINFO | SA_ONSTACK | SA_RESETHAND;
+    sigemptyset(&action.sa_mask);
+
+    int signals[] = { SIGSEGV, SIGABRT, SIGFPE, SIGBUS, SIGILL };
+    for (size_t i = 0; i < sizeof(signals)/sizeof(signals[0]); i++) {
+        sigaction(signals[i], &action, &PREVIOUS_HANDLERS[signals[i]]);
+    }
+}
+
+uint64_t elapsed_ns(const struct timespec* since){
+    struct timespec now;
+    clock_gettime(CLOCK_MONOTONIC, &now);
+
+    return (uint64_t)(now.tv_sec - since->tv_sec) * 1000000000ull 
+        + (uint64_t)now.tv_nsec - (uint64_t)since->tv_nsec;
+}
+
+void send_status(
+    const char* program_name, 
+    char* from_test, 
+    enum StatusType t, 
+    uint64_t duration_ns
+) {
     ProcessData data = {
         .info_type = Status,
         .stat = {
             .program_name = {0},
             .function_name = {0},
-            .t = t
+            .t = t,
+            .duration_ns = duration_ns
         }
     };
 
@@ -223,7 +922,30 @@ void send_status(const char* program_name, char* from_test, enum StatusType t) {
         &data,
         1,
         sizeof(ProcessData),
-        stdout
+        RUNNER_PIPE
+    );
+}
+
+void send_hello(const char* program_name){
+    ProcessData data = {
+        .info_type = Hello,
+        .hello = {
+            .program_name = {0}
+        }
+    };
+
+    snprintf(
+        data.hello.program_name, 
+        PROGRAM_NAME_MAX_CHAR_SIZE,
+        "%s", 
+        program_name
+    );
+
+    fwrite(
+        &data,
+        1,
+        sizeof(ProcessData),
+        RUNNER_PIPE
     );
 }
 
@@ -254,10 +976,193 @@ void send_register(const char* program_name, char* test_name){
         &data,
         1,
         sizeof(ProcessData),
-        stdout
+        RUNNER_PIPE
     );
 }
 
+void send_assertion(
+    const char* function_name, 
+    const char* expected, 
+    const char* actual,
+    const char* file,
+    int line
+){
+    ProcessData data;
+    memset(&data, 0, sizeof(ProcessData));
+    data.info_type = Assert;
+
+    // checks in test fixtures belong to the test they run for
+    if (CURRENT_TEST != NULL) {
+        function_name = CURRENT_TEST->thread_name;
+    }
+
+    snprintf(data.assert.program_name, PROGRAM_NAME_MAX_CHAR_SIZE, "%s", PROGRAM_NAME);
+    snprintf(data.assert.function_name, FUNCTION_MAX_CHAR_SIZE, "%s", function_name);
+    snprintf(data.assert.expected, MESSAGE_BUFFER, "%s", expected);
+    snprintf(data.assert.actual, MESSAGE_BUFFER, "%s", actual);
+    snprintf(data.assert.location, MESSAGE_BUFFER, "%s:%d", file, line);
+
+    fwrite(
+        &data,
+        1,
+        sizeof(ProcessData),
+        RUNNER_PIPE
+    );
+}
+
+void expectation_failed(const char* file, int line){
+    char msg[MESSAGE_BUFFER];
+    snprintf(msg, MESSAGE_BUFFER, "Expectation failed: %s:%d", file, line);
+
+    // suite fixtures have no test to fail
+    if (CURRENT_TEST == NULL) {
+        fprintf(stderr, "%s\n", msg);
+        return;
+    }
+
+    CURRENT_TEST->failed_expectations++;
+    send_warning_msg(PROGRAM_NAME, CURRENT_TEST->thread_name, msg);
+}
+
+void typed_expectation_failed(void){
+    if (CURRENT_TEST != NULL) {
+        CURRENT_TEST->failed_expectations++;
+    }
+}
+
+int assertion_eq_int(const char* func, const char* file, int line, long long expected, long long actual){
+    if (expected == actual) {
+        return 1;
+    }
+
+    char e[MESSAGE_BUFFER], a[MESSAGE_BUFFER];
+    snprintf(e, MESSAGE_BUFFER, "%lld", expected);
+    snprintf(a, MESSAGE_BUFFER, "%lld", actual);
+    send_assertion(func, e, a, file, line);
+    return 0;
+}
+
+int assertion_ne_int(const char* func, const char* file, int line, long long expected, long long actual){
+    if (expected != actual) {
+        return 1;
+    }
+
+    char e[MESSAGE_BUFFER], a[MESSAGE_BUFFER];
+    snprintf(e, MESSAGE_BUFFER, "not %lld", expected);
+    snprintf(a, MESSAGE_BUFFER, "%lld", actual);
+    send_assertion(func, e, a, file, line);
+    return 0;
+}
+
+// quoted string, or NULL
+static void format_str(char* out, const char* value){
+    if (value == NULL) {
+        snprintf(out, MESSAGE_BUFFER, "NULL");
+    } else {
+        snprintf(out, MESSAGE_BUFFER, "\"%s\"", value);
+    }
+}
+
+static int str_equal(const char* expected, const char* actual){
+    if (expected == NULL || actual == NULL) {
+        return expected == actual;
+    }
+    return strcmp(expected, actual) == 0;
+}
+
+int assertion_eq_str(const char* func, const char* file, int line, const char* expected, const char* actual){
+    if (str_equal(expected, actual)) {
+        return 1;
+    }
+
+    char e[MESSAGE_BUFFER], a[MESSAGE_BUFFER];
+    format_str(e, expected);
+    format_str(a, actual);
+    send_assertion(func, e, a, file, line);
+    return 0;
+}
+
+int assertion_ne_str(const char* func, const char* file, int line, const char* expected, const char* actual){
+    if (!str_equal(expected, actual)) {
+        return 1;
+    }
+
+    char e[MESSAGE_BUFFER], a[MESSAGE_BUFFER];
+    format_str(a, actual);
+    snprintf(e, MESSAGE_BUFFER, "not %.*s", MESSAGE_BUFFER - 5, a);
+    send_assertion(func, e, a, file, line);
+    return 0;
+}
+
+int assertion_near_double(const char* func, const char* file, int line, double expected, double actual, double tolerance){
+    double diff = expected > actual ? expected - actual : actual - expected;
+    if (diff <= tolerance) {
+        return 1;
+    }
+
+    char e[MESSAGE_BUFFER], a[MESSAGE_BUFFER];
+    snprintf(e, MESSAGE_BUFFER, "%g +/- %g", expected, tolerance);
+    snprintf(a, MESSAGE_BUFFER, "%g", actual);
+    send_assertion(func, e, a, file, line);
+    return 0;
+}
+
+// bytes starting at the first difference, as "@offset: 0a 0b ..."
+static void format_bytes(char* out, const unsigned char* bytes, size_t offset, size_t size){
+    int written = snprintf(out, MESSAGE_BUFFER, "@%zu:", offset);
+    for (size_t i = offset; i < size && i < offset + 8 && written < MESSAGE_BUFFER; i++) {
+        written += snprintf(out + written, MESSAGE_BUFFER - written, " %02x", bytes[i]);
+    }
+}
+
+int assertion_mem_eq(const char* func, const char* file, int line, const void* expected, const void* actual, size_t size){
+    if (expected == NULL || actual == NULL) {
+        if (expected == actual) {
+            return 1;
+        }
+        char e[MESSAGE_BUFFER], a[MESSAGE_BUFFER];
+        snprintf(e, MESSAGE_BUFFER, "%s", expected == NULL ? "NULL" : "memory");
+        snprintf(a, MESSAGE_BUFFER, "%s", actual == NULL ? "NULL" : "memory");
+        send_assertion(func, e, a, file, line);
+        return 0;
+    }
+
+    const unsigned char *e_bytes = expected, *a_bytes = actual;
+    size_t offset = 0;
+    while (offset < size && e_bytes[offset] == a_bytes[offset]) {
+        offset++;
+    }
+    if (offset == size) {
+        return 1;
+    }
+
+    char e[MESSAGE_BUFFER], a[MESSAGE_BUFFER];
+    format_bytes(e, e_bytes, offset, size);
+    format_bytes(a, a_bytes, offset, size);
+    send_assertion(func, e, a, file, line);
+    return 0;
+}
+
+int assertion_null(const char* func, const char* file, int line, const void* actual){
+    if (actual == NULL) {
+        return 1;
+    }
+
+    char a[MESSAGE_BUFFER];
+    snprintf(a, MESSAGE_BUFFER, "%p", actual);
+    send_assertion(func, "NULL", a, file, line);
+    return 0;
+}
+
+int assertion_not_null(const char* func, const char* file, int line, const void* actual){
+    if (actual != NULL) {
+        return 1;
+    }
+
+    send_assertion(func, "non-NULL", "NULL", file, line);
+    return 0;
+}
+
 void send_warning_msg(
     const char* program_name, 
     const char* function_name, 
@@ -281,7 +1186,7 @@ void send_warning_msg(
         &data,
         1,
         sizeof(ProcessData),
-        stdout
+        RUNNER_PIPE
     );
 }
 
@@ -294,6 +1199,7 @@ void send_warning_msg(
 
 
 #undef TEST_CASES
+#undef SERIAL_TEST_CASE
 #else
 #error "Undefined Test cases"
 
@@ -1,8 +1,32 @@
 #ifndef RUNTIME_SUPPORT
 #define RUNTIME_SUPPORT
 
+#include <stdio.h>
+#include <stdint.h>
 
 extern const char* PROGRAM_NAME;
+extern FILE* RUNNER_PIPE;
+
+// set by the runner to the descriptor receiving ProcessData frames
+#define PROTOCOL_FD_ENV "TESTRUNNER_PROTOCOL_FD"
+
+// program name reported to the runner, argv[0] is the
+// wrapped executable when the runner spawns it through e.g. valgrind
+#define PROGRAM_ENV "TESTRUNNER_PROGRAM"
+
+// newline separated test name patterns set by the runner
+#define TEST_FILTER_ENV "TESTRUNNER_TEST_FILTER"
+#define TEST_EXCLUDE_ENV "TESTRUNNER_TEST_EXCLUDE"
+#define REGEX_PATTERN_PREFIX "re:"
+
+// set by the runner when tests should be registered but not run
+#define LIST_ENV "TESTRUNNER_LIST"
+
+// set by the runner when every test should run in its own process
+#define ISOLATE_ENV "TESTRUNNER_ISOLATE"
+
+// set by the runner when the tests of a binary must run one at a time
+#define SERIAL_ENV "TESTRUNNER_SERIAL"
 
 
 /**
@@ -35,13 +59,21 @@ extern const char* PROGRAM_NAME;
 
 enum StatusType{
     Success,
-    Fail 
+    Fail,
+    // sent by the crash handler before the process dies
+    Crash,
+    // a setup or teardown fixture failed
+    SetupFail,
+    // still running when another test crashed the process
+    Interrupted
 };
 
 struct Status{
     char program_name[PROGRAM_NAME_MAX_CHAR_SIZE];
     char function_name[FUNCTION_MAX_CHAR_SIZE];
     enum StatusType t;
+    // time spent in the test thread
+    uint64_t duration_ns;
 };
 
 struct Register{
@@ -52,7 +84,9 @@ struct Register{
 enum LogType{
     Debug,
     Info, 
-    Warning
+    Warning,
+    // one frame of a crashed test's stack
+    Backtrace
 };
 
 struct Log{
@@ -61,13 +95,30 @@ struct Log{
     char msg[MESSAGE_BUFFER];
     enum LogType t;
 };
+// first frame of every run, tells the runner the
+// program speaks this protocol even when no test is registered
+struct Hello{
+    char program_name[PROGRAM_NAME_MAX_CHAR_SIZE];
+};
+
+// values of a failed typed assertion
+struct Assert{
+    char program_name[PROGRAM_NAME_MAX_CHAR_SIZE];
+    char function_name[FUNCTION_MAX_CHAR_SIZE];
+    char expected[MESSAGE_BUFFER];
+    char actual[MESSAGE_BUFFER];
+    // file:line of the assertion
+    char location[MESSAGE_BUFFER];
+};
 
 
 
 enum ProgramInfoType {
     Register = 0,
     Status = 1,
-    Log = 2
+    Log = 2,
+    Hello = 3,
+    Assert = 4
 };
 
 
@@ -76,6 +127,8 @@ typedef struct ProcessData{
         struct Log log;
         struct Register reg;
         struct Status stat;
+        struct Hello hello;
+        struct Assert assert;
     };
     enum ProgramInfoType info_type;
 } ProcessData;
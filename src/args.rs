@@ -0,0 +1,224 @@
+use std::fmt::Display;
+
+use crate::{
+    filter::{Filter, FilterErr, Matcher},
+    shard::Shard,
+};
+
+#[derive(Debug)]
+pub enum ArgsErr {
+    UnknownArgument(String),
+    MissingValue(String),
+    InvalidValue(String, String),
+    InvalidPattern(FilterErr),
+}
+
+impl Display for ArgsErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgsErr::UnknownArgument(arg) => write!(f, "unknown argument `{}`", arg),
+            ArgsErr::MissingValue(arg) => write!(f, "`{}` expects a value", arg),
+            ArgsErr::InvalidValue(arg, value) => {
+                write!(f, "invalid value `{}` for `{}`", value, arg)
+            }
+            ArgsErr::InvalidPattern(FilterErr::InvalidGlob(p)) => {
+                write!(f, "invalid glob pattern `{}`", p)
+            }
+            ArgsErr::InvalidPattern(FilterErr::InvalidRegex(p)) => {
+                write!(f, "invalid regex pattern `{}`", p)
+            }
+            ArgsErr::InvalidPattern(FilterErr::NonPosixRegex(p)) => {
+                write!(f, "test pattern `{}` is not a POSIX extended regex", p)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum Command {
+    #[default]
+    Run,
+    // enumerate tests without running them
+    List,
+    // combine several reports into one
+    Merge,
+    // pass/fail history and trends of past runs
+    History,
+    // run, then rerun executables rebuilt in bin_target
+    Watch,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+// Command line options of a single runner invocation
+#[derive(Debug, Default)]
+pub struct Args {
+    pub command: Command,
+    pub format: OutputFormat,
+    // file receiving the output instead of stdout
+    pub output: Option<String>,
+    // overwrite golden `.expected` files with the captured output
+    pub bless: bool,
+    // executables to run, matched against their collected path
+    pub exe_filter: Filter,
+    // tests started by the runtime, matched against the test name
+    pub test_filter: Filter,
+    // part of the executables run by this invocation
+    pub shard: Option<Shard>,
+    // report whose durations balance the shards
+    pub shard_timings: Option<String>,
+    // reports combined by `merge`
+    pub inputs: Vec<String>,
+    // only run the failed tests of the last report
+    pub rerun_failed: bool,
+    // report whose durations are compared against this run
+    pub baseline: Option<String>,
+}
+
+impl Args {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, ArgsErr> {
+        let mut parsed = Args::default();
+        let mut only_inputs = false;
+
+        while let Some(arg) = args.next() {
+            // after `merge` arguments other than options are reports, even
+            // ones named like a command, and after `--` every argument is
+            if parsed.command == Command::Merge && (only_inputs || !arg.starts_with("--")) {
+                parsed.inputs.push(arg);
+                continue;
+            }
+
+            match arg.as_str() {
+                "--" if parsed.command == Command::Merge => only_inputs = true,
+                "run" => parsed.command = Command::Run,
+                "list" => parsed.command = Command::List,
+                "merge" => parsed.command = Command::Merge,
+                "history" => parsed.command = Command::History,
+                "watch" => parsed.command = Command::Watch,
+                "--format" => {
+                    parsed.format = match next_value(&arg, &mut args)?.as_str() {
+                        "text" => OutputFormat::Text,
+                        "json" => OutputFormat::Json,
+                        value => return Err(ArgsErr::InvalidValue(arg, value.to_owned())),
+                    }
+                }
+                "--output" => parsed.output = Some(next_value(&arg, &mut args)?),
+                "--bless" => parsed.bless = true,
+                "--rerun-failed" => parsed.rerun_failed = true,
+                "--shard" => {
+                    let value = next_value(&arg, &mut args)?;
+                    parsed.shard =
+                        Some(Shard::parse(&value).ok_or(ArgsErr::InvalidValue(arg, value))?);
+                }
+                "--baseline" => parsed.baseline = Some(next_value(&arg, &mut args)?),
+                "--shard-timings" => parsed.shard_timings = Some(next_value(&arg, &mut args)?),
+                "--include" => parsed
+                    .exe_filter
+                    .include
+                    .push(next_matcher(&arg, &mut args)?),
+                "--exclude" => parsed
+                    .exe_filter
+                    .exclude
+                    .push(next_matcher(&arg, &mut args)?),
+                "--test" => parsed
+                    .test_filter
+                    .include
+                    .push(next_test_matcher(&arg, &mut args)?),
+                "--skip-test" => parsed
+                    .test_filter
+                    .exclude
+                    .push(next_test_matcher(&arg, &mut args)?),
+                _ => return Err(ArgsErr::UnknownArgument(arg)),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+fn next_value<I: Iterator<Item = String>>(arg: &str, args: &mut I) -> Result<String, ArgsErr> {
+    args.next()
+        .ok_or_else(|| ArgsErr::MissingValue(arg.to_owned()))
+}
+
+fn next_matcher<I: Iterator<Item = String>>(arg: &str, args: &mut I) -> Result<Matcher, ArgsErr> {
+    Matcher::parse(&next_value(arg, args)?).map_err(ArgsErr::InvalidPattern)
+}
+
+fn next_test_matcher<I: Iterator<Item = String>>(
+    arg: &str,
+    args: &mut I,
+) -> Result<Matcher, ArgsErr> {
+    Matcher::parse_test(&next_value(arg, args)?).map_err(ArgsErr::InvalidPattern)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, ArgsErr> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_command_and_options() {
+        let args = parse(&[
+            "list",
+            "--format",
+            "json",
+            "--include",
+            "bin/*",
+            "--shard",
+            "2/3",
+        ])
+        .unwrap();
+
+        assert_eq!(args.command, Command::List);
+        assert!(matches!(args.format, OutputFormat::Json));
+        assert_eq!(args.exe_filter.include.len(), 1);
+        assert_eq!(args.shard, Some(Shard { index: 2, count: 3 }));
+
+        assert_eq!(parse(&[]).unwrap().command, Command::Run);
+    }
+
+    #[test]
+    fn rejects_unknown_and_incomplete_arguments() {
+        assert!(matches!(
+            parse(&["--nope"]),
+            Err(ArgsErr::UnknownArgument(_))
+        ));
+        assert!(matches!(
+            parse(&["report.json"]),
+            Err(ArgsErr::UnknownArgument(_))
+        ));
+        assert!(matches!(
+            parse(&["--output"]),
+            Err(ArgsErr::MissingValue(_))
+        ));
+        assert!(matches!(
+            parse(&["--format", "xml"]),
+            Err(ArgsErr::InvalidValue(..))
+        ));
+        assert!(matches!(
+            parse(&["--shard", "4/3"]),
+            Err(ArgsErr::InvalidValue(..))
+        ));
+    }
+
+    #[test]
+    fn merge_takes_reports_named_like_commands() {
+        let args = parse(&["merge", "a.json", "run", "--format", "json", "list"]).unwrap();
+        assert_eq!(args.command, Command::Merge);
+        assert_eq!(args.inputs, ["a.json", "run", "list"]);
+        assert!(matches!(args.format, OutputFormat::Json));
+
+        let args = parse(&["merge", "a.json", "--", "--bless", "list"]).unwrap();
+        assert_eq!(args.inputs, ["a.json", "--bless", "list"]);
+        assert!(!args.bless);
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::mpsc,
+    time::SystemTime,
+};
+
+use termion::color;
+use threadpool::ThreadPool;
+use walkdir::WalkDir;
+
+use crate::{configs::ConfigBuild, coverage, get_global_config_ref, sanitizer};
+
+/// A test source and the executable it is compiled into
+#[derive(Debug, Clone)]
+struct BuildUnit {
+    source: PathBuf,
+    output: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum BuildOutcome {
+    Compiled,
+    // compiler output of a failed build
+    Failed(String),
+}
+
+/// Executables the build stage failed to produce, with the compiler output
+pub type BuildFailures = Vec<(String, String)>;
+
+// Sources below each source folder, placed at the same relative path in `bin_target`
+fn build_units(config: &ConfigBuild, bin_target: &Path) -> Vec<BuildUnit> {
+    let mut units = Vec::new();
+
+    for folder in config.sources.iter() {
+        for entry in WalkDir::new(folder).into_iter().flatten() {
+            let source = entry.path();
+            if !entry.file_type().is_file() || source.extension().is_none_or(|ext| ext != "c") {
+                continue;
+            }
+
+            let Ok(relative) = source.strip_prefix(folder) else {
+                continue;
+            };
+
+            units.push(BuildUnit {
+                source: source.to_path_buf(),
+                output: bin_target.join(relative.with_extension("")),
+            });
+        }
+    }
+
+    units
+}
+
+#[inline]
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Newest header of the include folders, a change rebuilds every unit
+fn newest_header(config: &ConfigBuild) -> Option<SystemTime> {
+    config
+        .include
+        .iter()
+        .flat_map(|folder| WalkDir::new(folder).into_iter().flatten())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "h"))
+        .filter_map(|entry| modified(entry.path()))
+        .max()
+}
+
+// File next to an output holding the command line it was compiled with
+fn stamp_path(unit: &BuildUnit) -> PathBuf {
+    let mut stamp = unit.output.clone().into_os_string();
+    stamp.push(".flags");
+    PathBuf::from(stamp)
+}
+
+// Compiler command line of a unit, `instrument` holds the sanitizer and
+// coverage flags so turning them on rebuilds every unit
+fn compile_command(config: &ConfigBuild, instrument: &[String], unit: &BuildUnit) -> Vec<String> {
+    let mut command = vec![config.compiler.clone()];
+    command.extend(config.flags.iter().cloned());
+    command.extend(instrument.iter().cloned());
+    command.extend(config.include.iter().map(|folder| format!("-I{}", folder)));
+    command.push("-o".to_owned());
+    command.push(unit.output.to_string_lossy().into_owned());
+    command.push(unit.source.to_string_lossy().into_owned());
+    command.extend(config.libs.iter().cloned());
+    command
+}
+
+fn up_to_date(unit: &BuildUnit, command: &[String], header: Option<SystemTime>) -> bool {
+    let Some(built) = modified(&unit.output) else {
+        return false;
+    };
+
+    fs::read_to_string(stamp_path(unit)).is_ok_and(|stamp| stamp == command.join("\n"))
+        && modified(&unit.source).is_some_and(|source| source <= built)
+        && header.is_none_or(|header| header <= built)
+}
+
+fn compile(unit: &BuildUnit, command: &[String]) -> io::Result<BuildOutcome> {
+    if let Some(parent) = unit.output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let output = Command::new(&command[0]).args(&command[1..]).output()?;
+
+    if output.status.success() {
+        fs::write(stamp_path(unit), command.join("\n"))?;
+        return Ok(BuildOutcome::Compiled);
+    }
+
+    // a stale executable would otherwise be run in place of the broken source
+    let _ = fs::remove_file(&unit.output);
+    let _ = fs::remove_file(stamp_path(unit));
+
+    Ok(BuildOutcome::Failed(
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    ))
+}
+
+/// Compiles the configured test sources into `bin_target`, skipping
+/// executables newer than their source and the included headers
+/// and built with the same command line
+pub fn build_sources(config: &'static ConfigBuild) -> BuildFailures {
+    let bin_target = Path::new(&get_global_config_ref().target_config.bin_target);
+    let header = newest_header(config);
+    let mut instrument = sanitizer::build_flags(&get_global_config_ref().sanitizer);
+    if get_global_config_ref().coverage.enabled {
+        instrument.push(coverage::COVERAGE_FLAG.to_owned());
+    }
+
+    let pending: Vec<(BuildUnit, Vec<String>)> = build_units(config, bin_target)
+        .into_iter()
+        .map(|unit| {
+            let command = compile_command(config, &instrument, &unit);
+            (unit, command)
+        })
+        .filter(|(unit, command)| !up_to_date(unit, command, header))
+        .collect();
+
+    println!(
+        "{}[ Building {} test sources... ]{}",
+        color::Fg(color::Yellow),
+        pending.len(),
+        color::Fg(color::Reset)
+    );
+
+    let pool = ThreadPool::new(get_global_config_ref().process.max_child_spawn.max(1));
+    let (tx, rx) = mpsc::channel();
+
+    for (unit, command) in pending {
+        let tx = tx.clone();
+        pool.execute(move || {
+            let outcome = compile(&unit, &command)
+                .unwrap_or_else(|e| BuildOutcome::Failed(format!("Unable to run compiler: {}", e)));
+            let _ = tx.send((unit, outcome));
+        });
+    }
+    drop(tx);
+
+    let mut failures = Vec::new();
+    for (unit, outcome) in rx {
+        let output = unit.output.to_string_lossy().into_owned();
+
+        match outcome {
+            BuildOutcome::Compiled => {}
+            BuildOutcome::Failed(stderr) => {
+                println!(
+                    "{}[ Build Failed: {} ]{}\n{}",
+                    color::Fg(color::Red),
+                    unit.source.display(),
+                    color::Fg(color::Reset),
+                    stderr
+                );
+                failures.push((output, stderr));
+            }
+        }
+    }
+
+    failures.sort();
+    failures
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn unit_in(dir: &Path) -> BuildUnit {
+        fs::create_dir_all(dir).unwrap();
+        let source = dir.join("add.c");
+        fs::write(&source, "int main(void){ return 0; }").unwrap();
+
+        BuildUnit {
+            source,
+            output: dir.join("bin").join("add"),
+        }
+    }
+
+    fn command(flags: &[&str]) -> Vec<String> {
+        flags.iter().map(|flag| flag.to_string()).collect()
+    }
+
+    #[test]
+    fn up_to_date_needs_same_command_and_newer_output() {
+        let dir = std::env::temp_dir().join(format!("build-test-{}", std::process::id()));
+        let unit = unit_in(&dir);
+        let plain = command(&["cc", "-o", "add", "add.c"]);
+
+        assert!(!up_to_date(&unit, &plain, None));
+
+        fs::create_dir_all(unit.output.parent().unwrap()).unwrap();
+        fs::write(&unit.output, "").unwrap();
+        assert!(!up_to_date(&unit, &plain, None), "built without a stamp");
+
+        fs::write(stamp_path(&unit), plain.join("\n")).unwrap();
+        assert!(up_to_date(&unit, &plain, None));
+
+        let sanitized = command(&["cc", "-fsanitize=address", "-o", "add", "add.c"]);
+        assert!(!up_to_date(&unit, &sanitized, None));
+
+        let header = modified(&unit.output).unwrap() + Duration::from_secs(1);
+        assert!(!up_to_date(&unit, &plain, Some(header)));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_compile_keeps_compiler_output_and_drops_stale_executable() {
+        let dir = std::env::temp_dir().join(format!("build-fail-{}", std::process::id()));
+        let unit = unit_in(&dir);
+        fs::create_dir_all(unit.output.parent().unwrap()).unwrap();
+        fs::write(&unit.output, "").unwrap();
+        fs::write(stamp_path(&unit), "cc").unwrap();
+
+        let broken = command(&["sh", "-c", "echo 'add.c:1: error: expected' >&2; exit 1"]);
+        let outcome = compile(&unit, &broken).unwrap();
+
+        assert!(
+            matches!(outcome, BuildOutcome::Failed(ref stderr) if stderr.contains("error: expected"))
+        );
+        assert!(!unit.output.exists());
+        assert!(!stamp_path(&unit).exists());
+
+        let missing = command(&["/nonexistent/cc"]);
+        assert!(compile(&unit, &missing).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
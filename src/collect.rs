@@ -1,11 +1,14 @@
-use std::{fs, io, path::Path};
+use std::{collections::BTreeMap, fs, path::Path};
 
 #[cfg(target_os = "linux")]
 use std::os::unix::fs::PermissionsExt;
 
 use walkdir::{DirEntry, WalkDir};
 
+use crate::filter::Filter;
+
 #[derive(Debug)]
+#[expect(dead_code, reason = "part of the collection API, not returned yet")]
 pub enum CollectErr {
     FolderReadProblem,
     InternalConversion,
@@ -14,10 +17,12 @@ pub enum CollectErr {
 type FileName = String;
 type FilePath = String;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FileCollection {
     pub exe_info: Vec<(FileName, FilePath)>,
     pub folder_group: Vec<(String, usize)>,
+    // tests started per executable, every selected test when absent
+    pub test_names: BTreeMap<FilePath, Vec<String>>,
 }
 
 impl FileCollection {
@@ -25,6 +30,7 @@ impl FileCollection {
         Self {
             exe_info: Vec::new(),
             folder_group: Vec::new(),
+            test_names: BTreeMap::new(),
         }
     }
 
@@ -56,6 +62,25 @@ impl FileCollection {
         self.exe_info.push((file_name, path_string));
 
         let parent_folder = FileCollection::get_parent(path);
+        self.push_group(parent_folder);
+    }
+
+    /// Keeps only the executables whose path satisfies `f`
+    pub fn retain<F: FnMut(&str) -> bool>(&mut self, mut f: F) {
+        let exe_info = std::mem::take(&mut self.exe_info);
+        self.folder_group.clear();
+
+        for (file_name, path) in exe_info.into_iter().filter(|e| f(&e.1)) {
+            self.push_group(FileCollection::get_parent(Path::new(&path)));
+            self.exe_info.push((file_name, path));
+        }
+
+        let exe_info = &self.exe_info;
+        self.test_names
+            .retain(|path, _| exe_info.iter().any(|(_, p)| p == path));
+    }
+
+    fn push_group(&mut self, parent_folder: &str) {
         if parent_folder.is_empty() {
             return;
         }
@@ -102,7 +127,10 @@ fn is_executable(path: &Path) -> bool {
     todo!()
 }
 
-pub fn collect_test_files<P: AsRef<Path>>(path: P) -> Result<FileCollection, CollectErr> {
+pub fn collect_test_files<P: AsRef<Path>>(
+    path: P,
+    filter: &Filter,
+) -> Result<FileCollection, CollectErr> {
     let mut exec_paths = FileCollection::new();
 
     let dir_walker = WalkDir::new(path).into_iter();
@@ -111,7 +139,10 @@ pub fn collect_test_files<P: AsRef<Path>>(path: P) -> Result<FileCollection, Col
             let path = dir.path();
             // println!("{:?}, {:?}", path.to_str(), path.is_file());
 
-            if path.is_file() && is_executable(path) {
+            if path.is_file()
+                && is_executable(path)
+                && path.to_str().is_some_and(|p| filter.accepts(p))
+            {
                 exec_paths.insert_path(path);
             }
         });
@@ -121,4 +152,39 @@ pub fn collect_test_files<P: AsRef<Path>>(path: P) -> Result<FileCollection, Col
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use super::*;
+
+    fn collection(paths: &[&str]) -> FileCollection {
+        let mut fc = FileCollection::new();
+        for path in paths {
+            fc.insert_path(Path::new(path));
+        }
+        fc
+    }
+
+    #[test]
+    fn insert_groups_consecutive_folders() {
+        let fc = collection(&["bin/a", "bin/b", "bin/db/c", "bin/d"]);
+
+        assert_eq!(fc.len(), 4);
+        assert_eq!(fc.exe_info[2], ("c".to_owned(), "bin/db/c".to_owned()));
+        assert_eq!(
+            fc.folder_group,
+            [("bin".to_owned(), 2), ("bin/db".to_owned(), 1), ("bin".to_owned(), 1)]
+        );
+    }
+
+    #[test]
+    fn retain_regroups_and_drops_test_names_of_removed_executables() {
+        let mut fc = collection(&["bin/a", "bin/db/b", "bin/db/c"]);
+        fc.test_names.insert("bin/a".to_owned(), vec!["add".to_owned()]);
+        fc.test_names.insert("bin/db/b".to_owned(), vec!["open".to_owned()]);
+
+        fc.retain(|path| path != "bin/db/b");
+
+        assert_eq!(fc.str_file_name_from(1), "bin/db/c");
+        assert_eq!(fc.folder_group, [("bin".to_owned(), 1), ("bin/db".to_owned(), 1)]);
+        assert_eq!(fc.test_names.keys().collect::<Vec<_>>(), ["bin/a"]);
+    }
+}
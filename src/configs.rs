@@ -0,0 +1,531 @@
+use std::collections::BTreeMap;
+
+use glob::{Pattern, PatternError};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct TargetConfig {
+    pub setting: TargetPath,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct TargetPath {
+    pub config_path: String,
+    pub bin_target: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    #[serde(skip_deserializing)]
+    pub target_config: TargetPath,
+    pub path: ConfigPath,
+    pub process: ConfigWorker,
+    #[serde(default)]
+    pub spawn: ConfigSpawn,
+    #[serde(default)]
+    pub golden: ConfigGolden,
+    #[serde(default)]
+    pub retry: ConfigRetry,
+    #[serde(default)]
+    pub regression: ConfigRegression,
+    pub build: Option<ConfigBuild>,
+    #[serde(default)]
+    pub sanitizer: ConfigSanitizer,
+    pub wrapper: Option<ConfigWrapper>,
+    #[serde(default)]
+    pub coverage: ConfigCoverage,
+    #[serde(default)]
+    pub core_dump: ConfigCoreDump,
+}
+
+// report out used for outputting test reports
+// error out are for internal error within the TestRunner
+#[derive(Debug, Deserialize)]
+pub struct ConfigPath {
+    pub report_out: String,
+    #[expect(
+        dead_code,
+        reason = "read from config.toml, internal errors still go to stderr"
+    )]
+    pub error_out: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ConfigWorker {
+    pub max_child_spawn: usize,
+    pub worker_count: usize,
+    #[serde(default)]
+    pub group: Vec<ConcurrencyGroup>,
+}
+
+// Caps how many executables matching the glob or folder run at once
+#[derive(Debug, Deserialize, Clone)]
+pub struct ConcurrencyGroup {
+    #[serde(rename = "match")]
+    pub pattern: PathPattern,
+    pub max_concurrent: usize,
+}
+
+// Arguments, environment and working directory of every spawned test binary.
+// `rule` entries are applied in order on top of the global values
+// for executables whose path matches the glob or lives under the folder.
+#[derive(Debug, Deserialize)]
+pub struct ConfigSpawn {
+    #[serde(default = "default_true")]
+    pub inherit_env: bool,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    pub stdin: Option<String>,
+    pub scratch_root: Option<String>,
+    #[serde(default)]
+    pub keep_failed_scratch: bool,
+    // run every test in its own process instead of a thread
+    #[serde(default)]
+    pub isolate: bool,
+    // run the tests of a binary one at a time
+    #[serde(default)]
+    pub serial: bool,
+    #[serde(default)]
+    pub rule: Vec<SpawnRule>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SpawnRule {
+    #[serde(rename = "match")]
+    pub pattern: PathPattern,
+    pub inherit_env: Option<bool>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    pub stdin: Option<String>,
+    pub isolate: Option<bool>,
+    pub serial: Option<bool>,
+}
+
+// Executables whose stdout is compared against `<executable>.expected`
+#[derive(Debug, Deserialize, Default)]
+pub struct ConfigGolden {
+    #[serde(default, rename = "match")]
+    pub patterns: Vec<PathPattern>,
+}
+
+// Failed tests are run again up to `count` times,
+// passing on a retry marks them flaky
+#[derive(Debug, Deserialize, Default)]
+pub struct ConfigRetry {
+    #[serde(default)]
+    pub count: usize,
+}
+
+// Durations compared against a `--baseline` report, a test or executable
+// regresses when it is slower by both the relative and absolute threshold
+#[derive(Debug, Deserialize)]
+pub struct ConfigRegression {
+    #[serde(default = "default_relative")]
+    pub relative: f64,
+    #[serde(default = "default_absolute_ms")]
+    pub absolute_ms: u64,
+    // regressions fail the test instead of adding a warning
+    #[serde(default)]
+    pub fail: bool,
+}
+
+// Compiles every `*.c` below `sources` into bin_target before running,
+// `include` should hold the folder of runtime.h and essential.h
+#[derive(Debug, Deserialize)]
+pub struct ConfigBuild {
+    pub sources: Vec<String>,
+    #[serde(default = "default_compiler")]
+    pub compiler: String,
+    #[serde(default)]
+    pub flags: Vec<String>,
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default = "default_libs")]
+    pub libs: Vec<String>,
+}
+
+// Sanitizers the build stage instruments with (address, undefined, thread,
+// leak), their reports are read from the captured stderr of each child
+#[derive(Debug, Deserialize)]
+pub struct ConfigSanitizer {
+    #[serde(default)]
+    pub enabled: Vec<String>,
+    // e.g. ASAN_OPTIONS, replacing the runner's defaults
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+    // stack frames kept per report
+    #[serde(default = "default_frames")]
+    pub frames: usize,
+}
+
+// Command the matching executables are run through, e.g. valgrind.
+// With `memcheck_xml` the memcheck errors become diagnostics
+#[derive(Debug, Deserialize)]
+pub struct ConfigWrapper {
+    pub command: Vec<String>,
+    // every executable when empty
+    #[serde(default, rename = "match")]
+    pub patterns: Vec<PathPattern>,
+    #[serde(default)]
+    pub memcheck_xml: bool,
+    #[serde(default = "default_frames")]
+    pub frames: usize,
+}
+
+// gcov coverage of the test sources, the build stage adds `--coverage`
+// and the reports are written to `<report_out>/coverage`
+#[derive(Debug, Deserialize)]
+pub struct ConfigCoverage {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_gcov")]
+    pub gcov: String,
+    // source files left out of the report, e.g. runtime.h
+    #[serde(default)]
+    pub exclude: Vec<PathPattern>,
+}
+
+// Crashing children dump their core into their working directory,
+// the debugger command is run as `<debugger> <executable> <core>`
+#[derive(Debug, Deserialize)]
+pub struct ConfigCoreDump {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_debugger")]
+    pub debugger: Vec<String>,
+    #[serde(default = "default_backtrace_frames")]
+    pub frames: usize,
+    // copy cores to `<report_out>/cores` before the scratch is removed
+    #[serde(default = "default_true")]
+    pub keep: bool,
+}
+
+/// Resolved spawn settings of a single executable,
+/// `cwd` falls back to the executable's scratch directory
+/// and `stdin` to the runner's own stdin
+#[derive(Debug, Default)]
+pub struct SpawnOptions {
+    pub env: BTreeMap<String, String>,
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    pub stdin: Option<String>,
+    pub isolate: bool,
+    pub serial: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_compiler() -> String {
+    "cc".to_owned()
+}
+
+fn default_libs() -> Vec<String> {
+    vec!["-lpthread".to_owned()]
+}
+
+fn default_frames() -> usize {
+    5
+}
+
+impl Default for ConfigSanitizer {
+    fn default() -> Self {
+        Self {
+            enabled: Vec::new(),
+            options: BTreeMap::new(),
+            frames: default_frames(),
+        }
+    }
+}
+
+fn default_gcov() -> String {
+    "gcov".to_owned()
+}
+
+impl Default for ConfigCoverage {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            gcov: default_gcov(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+fn default_debugger() -> Vec<String> {
+    ["gdb", "-batch", "-ex", "bt"].map(str::to_owned).to_vec()
+}
+
+fn default_backtrace_frames() -> usize {
+    10
+}
+
+impl Default for ConfigCoreDump {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            debugger: default_debugger(),
+            frames: default_backtrace_frames(),
+            keep: true,
+        }
+    }
+}
+
+fn default_relative() -> f64 {
+    0.5
+}
+
+fn default_absolute_ms() -> u64 {
+    10
+}
+
+impl Default for ConfigRegression {
+    fn default() -> Self {
+        Self {
+            relative: default_relative(),
+            absolute_ms: default_absolute_ms(),
+            fail: false,
+        }
+    }
+}
+
+impl Default for ConfigSpawn {
+    fn default() -> Self {
+        Self {
+            inherit_env: true,
+            env: BTreeMap::new(),
+            args: Vec::new(),
+            cwd: None,
+            stdin: None,
+            scratch_root: None,
+            keep_failed_scratch: false,
+            isolate: false,
+            serial: false,
+            rule: Vec::new(),
+        }
+    }
+}
+
+/// Glob matched against executable or source paths, compiled once
+/// when the config is loaded. A pattern naming a folder also matches
+/// everything below it
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct PathPattern {
+    glob: Pattern,
+}
+
+impl PathPattern {
+    pub fn new(pattern: &str) -> Result<Self, PatternError> {
+        Ok(Self {
+            glob: Pattern::new(pattern)?,
+        })
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.glob.as_str()
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let folder = self.as_str().trim_end_matches('/');
+        if path
+            .strip_prefix(folder)
+            .is_some_and(|rest| rest.starts_with('/'))
+        {
+            return true;
+        }
+
+        self.glob.matches(path)
+    }
+}
+
+impl TryFrom<String> for PathPattern {
+    type Error = PatternError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        PathPattern::new(&value)
+    }
+}
+
+impl SpawnRule {
+    #[inline]
+    pub fn matches(&self, exe_path: &str) -> bool {
+        self.pattern.matches(exe_path)
+    }
+}
+
+impl ConfigWorker {
+    /// Whether `exe_path` can start next to the `running` executables
+    /// without exceeding the cap of one of its groups
+    pub fn admits(&self, exe_path: &str, running: &[&str]) -> bool {
+        self.group
+            .iter()
+            .filter(|g| g.pattern.matches(exe_path))
+            .all(|g| {
+                running.iter().filter(|r| g.pattern.matches(r)).count() < g.max_concurrent.max(1)
+            })
+    }
+}
+
+impl ConfigSanitizer {
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        !self.enabled.is_empty()
+    }
+}
+
+impl ConfigWrapper {
+    pub fn wraps(&self, exe_path: &str) -> bool {
+        !self.command.is_empty()
+            && (self.patterns.is_empty() || self.patterns.iter().any(|p| p.matches(exe_path)))
+    }
+}
+
+impl ConfigCoverage {
+    pub fn excludes(&self, source: &str) -> bool {
+        self.exclude.iter().any(|p| p.matches(source))
+    }
+}
+
+impl ConfigGolden {
+    pub fn matches(&self, exe_path: &str) -> bool {
+        self.patterns.iter().any(|p| p.matches(exe_path))
+    }
+}
+
+impl ConfigSpawn {
+    pub fn resolve(&self, exe_path: &str) -> SpawnOptions {
+        let rules: Vec<&SpawnRule> = self.rule.iter().filter(|r| r.matches(exe_path)).collect();
+
+        let inherit_env = rules
+            .iter()
+            .rev()
+            .find_map(|r| r.inherit_env)
+            .unwrap_or(self.inherit_env);
+
+        let mut options = SpawnOptions {
+            isolate: rules
+                .iter()
+                .rev()
+                .find_map(|r| r.isolate)
+                .unwrap_or(self.isolate),
+            serial: rules
+                .iter()
+                .rev()
+                .find_map(|r| r.serial)
+                .unwrap_or(self.serial),
+            ..Default::default()
+        };
+        if inherit_env {
+            options.env.extend(std::env::vars());
+        }
+
+        options.env.extend(self.env.clone());
+        options.args.extend(self.args.iter().cloned());
+        options.cwd = self.cwd.clone();
+        options.stdin = self.stdin.clone();
+
+        for rule in rules {
+            options.env.extend(rule.env.clone());
+            options.args.extend(rule.args.iter().cloned());
+            if rule.cwd.is_some() {
+                options.cwd = rule.cwd.clone();
+            }
+            if rule.stdin.is_some() {
+                options.stdin = rule.stdin.clone();
+            }
+        }
+
+        options
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rule(pattern: &str) -> SpawnRule {
+        SpawnRule {
+            pattern: PathPattern::new(pattern).unwrap(),
+            inherit_env: None,
+            env: BTreeMap::new(),
+            args: Vec::new(),
+            cwd: None,
+            stdin: None,
+            isolate: None,
+            serial: None,
+        }
+    }
+
+    #[test]
+    fn rule_matches_folder_and_glob() {
+        assert!(rule("bin/io").matches("bin/io/read"));
+        assert!(rule("bin/io/").matches("bin/io/nested/read"));
+        assert!(!rule("bin/io").matches("bin/iox/read"));
+        assert!(rule("bin/*_net").matches("bin/tcp_net"));
+        assert!(!rule("bin/*_net").matches("bin/tcp"));
+        assert!(PathPattern::new("bin/[").is_err());
+    }
+
+    #[test]
+    fn groups_cap_concurrency() {
+        let process = ConfigWorker {
+            max_child_spawn: 4,
+            worker_count: 1,
+            group: vec![ConcurrencyGroup {
+                pattern: PathPattern::new("bin/db").unwrap(),
+                max_concurrent: 1,
+            }],
+        };
+
+        assert!(process.admits("bin/db/a", &["bin/net/a"]));
+        assert!(!process.admits("bin/db/b", &["bin/db/a"]));
+        assert!(process.admits("bin/net/b", &["bin/db/a", "bin/net/a"]));
+    }
+
+    #[test]
+    fn resolve_layers_rules_in_order() {
+        let mut first = rule("bin");
+        first.inherit_env = Some(false);
+        first.env.insert("MODE".into(), "first".into());
+        first.args.push("-a".into());
+        first.isolate = Some(true);
+
+        let mut second = rule("bin/*");
+        second.env.insert("MODE".into(), "second".into());
+        second.args.push("-b".into());
+
+        let spawn = ConfigSpawn {
+            inherit_env: true,
+            env: BTreeMap::from([("LANG".to_string(), "C".to_string())]),
+            args: vec!["-g".into()],
+            rule: vec![first, second],
+            ..Default::default()
+        };
+
+        let options = spawn.resolve("bin/prog");
+        assert_eq!(
+            options.env,
+            BTreeMap::from([
+                ("LANG".to_string(), "C".to_string()),
+                ("MODE".to_string(), "second".to_string()),
+            ])
+        );
+        assert_eq!(options.args, vec!["-g", "-a", "-b"]);
+        assert!(options.isolate);
+
+        let untouched = spawn.resolve("other/prog");
+        assert_eq!(untouched.args, vec!["-g"]);
+        assert!(!untouched.isolate);
+        assert_eq!(untouched.env.get("LANG").map(String::as_str), Some("C"));
+        assert!(!untouched.env.contains_key("MODE"));
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::LazyLock,
+};
+
+use nix::libc;
+use regex::Regex;
+
+use crate::{
+    configs::ConfigCoreDump,
+    get_global_config_ref,
+    record_collection::{Diagnostic, StackFrame},
+};
+
+const CORES_DIR: &str = "cores";
+const CORE_PATTERN: &str = "/proc/sys/kernel/core_pattern";
+
+static FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^#\d+\s+(?:0x[0-9a-f]+ in )?(\S+) \(.*?\)(?: (?:at|from) (\S+))?")
+        .expect("valid backtrace frame")
+});
+
+/// Command raising the core size limit to the hard limit, then executing
+/// its arguments in the same process. Prepended to the command of every
+/// child so the runner keeps its own limit
+pub fn core_limit_command() -> [String; 3] {
+    [
+        "/bin/sh".to_owned(),
+        "-c".to_owned(),
+        r#"ulimit -c "$(ulimit -H -c)"; exec "$0" "$@""#.to_owned(),
+    ]
+}
+
+/// Why cores can't be found in the working directory of the crashed
+/// process with the current `core_pattern`, None when they can
+pub fn core_pattern_issue() -> Option<String> {
+    let pattern = fs::read_to_string(CORE_PATTERN).ok()?;
+    pattern_issue(pattern.trim())
+}
+
+fn pattern_issue(pattern: &str) -> Option<String> {
+    if let Some(handler) = pattern.strip_prefix('|') {
+        let uses_coredumpctl = handler.contains("systemd-coredump");
+        return Some(format!(
+            "core_pattern pipes cores to `{}`{}",
+            handler,
+            if uses_coredumpctl {
+                ", reading them back with coredumpctl"
+            } else {
+                ", no core will be found"
+            }
+        ));
+    }
+    if pattern.contains('/') {
+        return Some(format!("core_pattern writes cores to `{}`", pattern));
+    }
+    if pattern != "core" && pattern != "core.%p" {
+        return Some(format!(
+            "core_pattern `{}` names cores other than core or core.<pid>",
+            pattern
+        ));
+    }
+
+    None
+}
+
+/// Core written by `pid` into `dir` with the default `core` or `core.<pid>`
+/// pattern, or one stored by systemd-coredump, dumped into `dir`
+pub fn find_core(dir: &Path, pid: libc::pid_t) -> Option<PathBuf> {
+    [dir.join(format!("core.{}", pid)), dir.join("core")]
+        .into_iter()
+        .find(|core| core.is_file())
+        .or_else(|| coredumpctl_core(dir, pid))
+}
+
+fn coredumpctl_core(dir: &Path, pid: libc::pid_t) -> Option<PathBuf> {
+    let core = dir.join(format!("core.{}", pid));
+    let dumped = Command::new("coredumpctl")
+        .args(["--quiet", "--no-pager", "dump"])
+        .arg(pid.to_string())
+        .arg("--output")
+        .arg(&core)
+        .output()
+        .ok()?;
+
+    (dumped.status.success() && core.is_file()).then_some(core)
+}
+
+/// Frames of the debugger's backtrace output
+pub fn parse_backtrace(output: &str, max_frames: usize) -> Vec<StackFrame> {
+    output
+        .lines()
+        .filter_map(|line| FRAME.captures(line))
+        .take(max_frames)
+        .map(|frame| StackFrame {
+            function: Some(frame[1].to_owned()).filter(|f| f != "??"),
+            location: frame
+                .get(2)
+                .map_or_else(|| "??".to_owned(), |l| l.as_str().to_owned()),
+        })
+        .collect()
+}
+
+// Moves the core out of the scratch directory, which is removed after the run.
+// The pid keeps cores of retried runs apart
+fn keep_core(core: &Path, exe_path: &str, pid: libc::pid_t) -> io::Result<PathBuf> {
+    let dir = Path::new(&get_global_config_ref().path.report_out).join(CORES_DIR);
+    fs::create_dir_all(&dir)?;
+
+    let kept = dir.join(format!(
+        "{}.{}.core",
+        exe_path.trim_start_matches("./").replace('/', "_"),
+        pid
+    ));
+    if fs::rename(core, &kept).is_err() {
+        fs::copy(core, &kept)?;
+    }
+
+    Ok(kept)
+}
+
+/// Backtrace of the crashed executable out of its core,
+/// without frames when no debugger could be run
+pub fn inspect_core(
+    config: &ConfigCoreDump,
+    exe_path: &str,
+    pid: libc::pid_t,
+    core: &Path,
+    signal: i32,
+) -> Diagnostic {
+    let exe = fs::canonicalize(exe_path).unwrap_or_else(|_| PathBuf::from(exe_path));
+
+    let backtrace = config.debugger.split_first().map(|(debugger, args)| {
+        Command::new(debugger)
+            .args(args)
+            .arg(&exe)
+            .arg(core)
+            .output()
+    });
+
+    let (frames, note) = match backtrace {
+        Some(Ok(output)) => (
+            parse_backtrace(&String::from_utf8_lossy(&output.stdout), config.frames),
+            String::new(),
+        ),
+        Some(Err(e)) => (Vec::new(), format!(", debugger unavailable: {}", e)),
+        None => (Vec::new(), String::new()),
+    };
+
+    let core = if config.keep {
+        keep_core(core, exe_path, pid).unwrap_or_else(|_| core.to_path_buf())
+    } else {
+        core.to_path_buf()
+    };
+
+    Diagnostic {
+        tool: "core dump".to_owned(),
+        kind: format!("signal {}", signal),
+        message: format!(
+            "Terminated by signal {}, core at {}{}",
+            signal,
+            core.display(),
+            note
+        ),
+        test: None,
+        frames,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn flags_core_patterns_writing_elsewhere() {
+        assert_eq!(pattern_issue("core"), None);
+        assert_eq!(pattern_issue("core.%p"), None);
+        assert!(pattern_issue("|/usr/share/apport/apport -p%p").is_some());
+        assert!(
+            pattern_issue("|/lib/systemd/systemd-coredump %P %u")
+                .unwrap()
+                .contains("coredumpctl")
+        );
+        assert!(pattern_issue("/var/crash/core.%e").is_some());
+    }
+
+    #[test]
+    fn parses_gdb_backtrace() {
+        let output = "\
+[New LWP 1234]
+Core was generated by `/tmp/bt/program'.
+Program terminated with signal SIGSEGV, Segmentation fault.
+#0  0x000055555555518d in crash (arg=0x0) at /tmp/src/program.c:12
+#1  0x00007ffff7e2a1f4 in start_thread (arg=<optimized out>) at ./nptl/pthread_create.c:442
+#2  0x00007ffff7eaa8eb in ?? () from /lib/x86_64-linux-gnu/libc.so.6
+";
+
+        let frames = parse_backtrace(output, 10);
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].function.as_deref(), Some("crash"));
+        assert_eq!(frames[0].location, "/tmp/src/program.c:12");
+        assert_eq!(frames[2].function, None);
+        assert_eq!(frames[2].location, "/lib/x86_64-linux-gnu/libc.so.6");
+        assert_eq!(parse_backtrace(output, 1).len(), 1);
+    }
+}
@@ -0,0 +1,450 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Deserialize;
+use termion::color;
+use walkdir::WalkDir;
+
+use crate::{configs::ConfigCoverage, get_global_config_ref};
+
+const COVERAGE_DIR: &str = "coverage";
+// every executable writes its `.gcda` files below its own prefix
+const DATA_DIR: &str = "data";
+pub const LCOV_FILE: &str = "lcov.info";
+pub const COBERTURA_FILE: &str = "cobertura.xml";
+
+/// Compiler flag instrumenting test sources
+pub const COVERAGE_FLAG: &str = "--coverage";
+
+#[derive(Debug, Deserialize)]
+struct GcovOutput {
+    current_working_directory: String,
+    files: Vec<GcovFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GcovFile {
+    file: String,
+    lines: Vec<GcovLine>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GcovLine {
+    line_number: u32,
+    count: u64,
+    #[serde(default)]
+    branches: Vec<GcovBranch>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GcovBranch {
+    count: u64,
+}
+
+/// Hits of a single source file summed over every executable
+#[derive(Debug, Default)]
+pub struct FileCoverage {
+    pub lines: BTreeMap<u32, u64>,
+    // hits of each branch leaving a line
+    pub branches: BTreeMap<u32, Vec<u64>>,
+}
+
+#[derive(Debug, Default)]
+pub struct Coverage {
+    pub files: BTreeMap<String, FileCoverage>,
+}
+
+// (covered, total) of lines and branches
+type Counts = ((usize, usize), (usize, usize));
+
+impl FileCoverage {
+    fn counts(&self) -> Counts {
+        let lines = (
+            self.lines.values().filter(|hits| **hits > 0).count(),
+            self.lines.len(),
+        );
+        let branches = self
+            .branches
+            .values()
+            .flatten()
+            .fold((0, 0), |(hit, total), hits| {
+                (hit + usize::from(*hits > 0), total + 1)
+            });
+
+        (lines, branches)
+    }
+}
+
+impl Coverage {
+    fn add(&mut self, output: GcovOutput, config: &ConfigCoverage) {
+        for file in output.files {
+            let path = Path::new(&output.current_working_directory).join(&file.file);
+            let path = path.to_string_lossy().into_owned();
+            if config.excludes(&path) {
+                continue;
+            }
+
+            let coverage = self.files.entry(path).or_default();
+            for line in file.lines {
+                *coverage.lines.entry(line.line_number).or_default() += line.count;
+
+                if line.branches.is_empty() {
+                    continue;
+                }
+                let branches = coverage.branches.entry(line.line_number).or_default();
+                branches.resize(branches.len().max(line.branches.len()), 0);
+                for (total, branch) in branches.iter_mut().zip(line.branches.iter()) {
+                    *total += branch.count;
+                }
+            }
+        }
+    }
+
+    fn counts(&self) -> Counts {
+        self.files.values().map(FileCoverage::counts).fold(
+            ((0, 0), (0, 0)),
+            |((lh, lt), (bh, bt)), ((h, t), (bh2, bt2))| ((lh + h, lt + t), (bh + bh2, bt + bt2)),
+        )
+    }
+
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+        for (path, file) in self.files.iter() {
+            let ((lines_hit, lines), (branches_hit, branches)) = file.counts();
+            let _ = writeln!(
+                out,
+                "\t{}\tlines: {}/{} ({:.1}%)\tbranches: {}/{} ({:.1}%)",
+                path,
+                lines_hit,
+                lines,
+                percent(lines_hit, lines),
+                branches_hit,
+                branches,
+                percent(branches_hit, branches)
+            );
+        }
+
+        let ((lines_hit, lines), (branches_hit, branches)) = self.counts();
+        let _ = writeln!(
+            out,
+            "Total\tlines: {}/{} ({:.1}%)\tbranches: {}/{} ({:.1}%)",
+            lines_hit,
+            lines,
+            percent(lines_hit, lines),
+            branches_hit,
+            branches,
+            percent(branches_hit, branches)
+        );
+
+        out
+    }
+
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::new();
+        for (path, file) in self.files.iter() {
+            let ((lines_hit, lines), (branches_hit, branches)) = file.counts();
+
+            let _ = writeln!(out, "TN:\nSF:{}", path);
+            for (line, hits) in file.branches.iter() {
+                for (branch, count) in hits.iter().enumerate() {
+                    let _ = writeln!(out, "BRDA:{},0,{},{}", line, branch, count);
+                }
+            }
+            let _ = writeln!(out, "BRF:{}\nBRH:{}", branches, branches_hit);
+            for (line, hits) in file.lines.iter() {
+                let _ = writeln!(out, "DA:{},{}", line, hits);
+            }
+            let _ = writeln!(out, "LF:{}\nLH:{}\nend_of_record", lines, lines_hit);
+        }
+
+        out
+    }
+
+    pub fn to_cobertura(&self) -> String {
+        let ((lines_hit, lines), (branches_hit, branches)) = self.counts();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let mut out = String::new();
+        let _ = writeln!(out, r#"<?xml version="1.0" ?>"#);
+        let _ = writeln!(
+            out,
+            r#"<coverage line-rate="{:.4}" branch-rate="{:.4}" lines-covered="{}" lines-valid="{}" branches-covered="{}" branches-valid="{}" complexity="0" version="{}" timestamp="{}">"#,
+            rate(lines_hit, lines),
+            rate(branches_hit, branches),
+            lines_hit,
+            lines,
+            branches_hit,
+            branches,
+            env!("CARGO_PKG_VERSION"),
+            timestamp
+        );
+        let _ = writeln!(out, "  <sources><source>/</source></sources>");
+        let _ = writeln!(
+            out,
+            r#"  <packages><package name="tests" line-rate="{:.4}" branch-rate="{:.4}" complexity="0"><classes>"#,
+            rate(lines_hit, lines),
+            rate(branches_hit, branches)
+        );
+
+        for (path, file) in self.files.iter() {
+            let ((lines_hit, lines), (branches_hit, branches)) = file.counts();
+            let name = Path::new(path)
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.clone());
+
+            let _ = writeln!(
+                out,
+                r#"    <class name="{}" filename="{}" line-rate="{:.4}" branch-rate="{:.4}" complexity="0"><methods/><lines>"#,
+                escape_xml(&name),
+                escape_xml(path.trim_start_matches('/')),
+                rate(lines_hit, lines),
+                rate(branches_hit, branches)
+            );
+            for (line, hits) in file.lines.iter() {
+                match file.branches.get(line) {
+                    Some(branches) => {
+                        let taken = branches.iter().filter(|b| **b > 0).count();
+                        let _ = writeln!(
+                            out,
+                            r#"      <line number="{}" hits="{}" branch="true" condition-coverage="{:.0}% ({}/{})"/>"#,
+                            line,
+                            hits,
+                            percent(taken, branches.len()),
+                            taken,
+                            branches.len()
+                        );
+                    }
+                    None => {
+                        let _ = writeln!(
+                            out,
+                            r#"      <line number="{}" hits="{}" branch="false"/>"#,
+                            line, hits
+                        );
+                    }
+                }
+            }
+            let _ = writeln!(out, "    </lines></class>");
+        }
+
+        let _ = writeln!(out, "  </classes></package></packages>\n</coverage>");
+        out
+    }
+}
+
+#[inline]
+fn rate(hit: usize, total: usize) -> f64 {
+    if total == 0 {
+        1.0
+    } else {
+        hit as f64 / total as f64
+    }
+}
+
+#[inline]
+fn percent(hit: usize, total: usize) -> f64 {
+    rate(hit, total) * 100.0
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Absolute since children run inside their scratch directory
+fn coverage_dir() -> PathBuf {
+    let dir = Path::new(&get_global_config_ref().path.report_out).join(COVERAGE_DIR);
+    std::path::absolute(&dir).unwrap_or(dir)
+}
+
+/// `GCOV_PREFIX` of an executable, keeping parallel children
+/// from writing to the same `.gcda` files
+pub fn gcov_prefix(exe_path: &str) -> PathBuf {
+    prefix_in(&coverage_dir().join(DATA_DIR), exe_path)
+}
+
+// Folder of an executable below `data_dir`, the path is percent-encoded
+// so that no two executables share a folder
+fn prefix_in(data_dir: &Path, exe_path: &str) -> PathBuf {
+    let name = exe_path
+        .trim_start_matches("./")
+        .replace('%', "%25")
+        .replace('/', "%2F");
+
+    data_dir.join(name)
+}
+
+/// Removes the coverage data of previous runs
+pub fn reset_data() -> io::Result<()> {
+    match fs::remove_dir_all(coverage_dir().join(DATA_DIR)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+// Runs gcov on a data file below `prefix`, the `.gcno` written at build time
+// is copied next to it first since gcov expects both in one folder
+fn run_gcov(config: &ConfigCoverage, prefix: &Path, gcda: &Path) -> io::Result<GcovOutput> {
+    let relative = gcda.strip_prefix(prefix).map_err(io::Error::other)?;
+    let gcno = Path::new("/").join(relative).with_extension("gcno");
+    fs::copy(&gcno, gcda.with_extension("gcno"))?;
+
+    let output = Command::new(&config.gcov)
+        .args(["--json-format", "--stdout", "--branch-probabilities"])
+        .arg(gcda)
+        .current_dir(gcda.parent().unwrap_or(prefix))
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Aggregates the coverage data written by every executable
+pub fn collect_coverage(config: &ConfigCoverage) -> io::Result<Coverage> {
+    collect_in(config, &coverage_dir().join(DATA_DIR))
+}
+
+fn collect_in(config: &ConfigCoverage, data_dir: &Path) -> io::Result<Coverage> {
+    let mut coverage = Coverage::default();
+    if !data_dir.exists() {
+        return Ok(coverage);
+    }
+
+    for prefix in fs::read_dir(data_dir)? {
+        let prefix = prefix?.path();
+
+        for entry in WalkDir::new(&prefix).into_iter().flatten() {
+            if entry.path().extension().is_none_or(|ext| ext != "gcda") {
+                continue;
+            }
+
+            match run_gcov(config, &prefix, entry.path()) {
+                Ok(output) => coverage.add(output, config),
+                Err(e) => eprintln!(
+                    "Unable to read coverage of {}: {}",
+                    entry.path().display(),
+                    e
+                ),
+            }
+        }
+    }
+
+    Ok(coverage)
+}
+
+/// Prints the coverage summary and writes the lcov and Cobertura reports
+pub fn report_coverage(config: &ConfigCoverage) -> io::Result<()> {
+    let coverage = collect_coverage(config)?;
+
+    println!(
+        "{}[ Coverage ]{}\n{}",
+        color::Fg(color::Yellow),
+        color::Fg(color::Reset),
+        coverage.summary()
+    );
+
+    let dir = coverage_dir();
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(LCOV_FILE), coverage.to_lcov())?;
+    fs::write(dir.join(COBERTURA_FILE), coverage.to_cobertura())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sums_hits_of_every_executable() {
+        let json = r#"{"current_working_directory": "/src", "files": [{"file": "a.c",
+            "lines": [{"line_number": 3, "count": 0, "branches": [{"count": 1}, {"count": 0}]},
+                      {"line_number": 4, "count": 2, "branches": []}]}]}"#;
+
+        let mut coverage = Coverage::default();
+        coverage.add(
+            serde_json::from_str(json).unwrap(),
+            &ConfigCoverage::default(),
+        );
+        coverage.add(
+            serde_json::from_str(json).unwrap(),
+            &ConfigCoverage::default(),
+        );
+
+        let file = &coverage.files["/src/a.c"];
+        assert_eq!(file.lines[&4], 4);
+        assert_eq!(file.branches[&3], vec![2, 0]);
+        assert_eq!(coverage.counts(), ((1, 2), (1, 2)));
+
+        let lcov = coverage.to_lcov();
+        assert!(lcov.contains("SF:/src/a.c\nBRDA:3,0,0,2\nBRDA:3,0,1,0\nBRF:2\nBRH:1\n"));
+        assert!(lcov.contains("DA:3,0\nDA:4,4\nLF:2\nLH:1\nend_of_record"));
+    }
+
+    #[test]
+    fn prefixes_of_executables_never_collide() {
+        let data = Path::new("/data");
+
+        assert_ne!(prefix_in(data, "a/b_c"), prefix_in(data, "a_b/c"));
+        assert_ne!(prefix_in(data, "a/b"), prefix_in(data, "a%2Fb"));
+    }
+
+    // Builds an instrumented program and runs it from another directory,
+    // the way the spawner does, then reads its coverage back
+    #[test]
+    fn collects_coverage_of_instrumented_binary() {
+        let root = std::env::temp_dir().join(format!("coverage-test-{}", std::process::id()));
+        let build = root.join("build");
+        let scratch = root.join("scratch");
+        let data = root.join("data");
+        fs::create_dir_all(&build).unwrap();
+        fs::create_dir_all(&scratch).unwrap();
+
+        let source = build.join("covered.c");
+        fs::write(&source, "int main(int argc, char **argv) {\n    if (argc > 5) {\n        return 1;\n    }\n    return 0;\n}\n").unwrap();
+        let exe = build.join("covered");
+        let built = Command::new("gcc")
+            .arg(COVERAGE_FLAG)
+            .arg(&source)
+            .arg("-o")
+            .arg(&exe)
+            .current_dir(&build)
+            .status();
+        if !built.is_ok_and(|status| status.success()) {
+            eprintln!("gcc unavailable, skipping");
+            let _ = fs::remove_dir_all(&root);
+            return;
+        }
+
+        let ran = Command::new(&exe)
+            .env("GCOV_PREFIX", prefix_in(&data, &exe.to_string_lossy()))
+            .current_dir(&scratch)
+            .status()
+            .unwrap();
+        assert!(ran.success());
+
+        let coverage = collect_in(&ConfigCoverage::default(), &data).unwrap();
+        let file = &coverage.files[&source.to_string_lossy().into_owned()];
+        assert_eq!(file.lines[&2], 1);
+        assert_eq!(file.lines[&3], 0);
+        assert_eq!(file.branches[&2], vec![0, 1]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,199 @@
+use regex::Regex;
+
+use crate::configs::PathPattern;
+
+/// Environment variables telling the runtime which tests to start,
+/// each holds newline separated patterns
+pub const TEST_FILTER_ENV: &str = "TESTRUNNER_TEST_FILTER";
+pub const TEST_EXCLUDE_ENV: &str = "TESTRUNNER_TEST_EXCLUDE";
+
+const REGEX_PREFIX: &str = "re:";
+
+#[derive(Debug)]
+pub enum FilterErr {
+    InvalidGlob(String),
+    InvalidRegex(String),
+    NonPosixRegex(String),
+}
+
+// Glob by default, `re:` switches the pattern to a regex
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    Glob(PathPattern),
+    Regex(Regex),
+}
+
+impl Matcher {
+    pub fn parse(pattern: &str) -> Result<Self, FilterErr> {
+        if let Some(regex) = pattern.strip_prefix(REGEX_PREFIX) {
+            return Regex::new(regex)
+                .map(Matcher::Regex)
+                .map_err(|_| FilterErr::InvalidRegex(pattern.to_owned()));
+        }
+
+        PathPattern::new(pattern)
+            .map(Matcher::Glob)
+            .map_err(|_| FilterErr::InvalidGlob(pattern.to_owned()))
+    }
+
+    /// Test name patterns are matched again by runtime.h with fnmatch and
+    /// POSIX extended regular expressions, so `re:` patterns are limited
+    /// to the syntax both sides read the same way
+    pub fn parse_test(pattern: &str) -> Result<Self, FilterErr> {
+        let matcher = Self::parse(pattern)?;
+        if let Matcher::Regex(regex) = &matcher
+            && !is_posix_ere(regex.as_str())
+        {
+            return Err(FilterErr::NonPosixRegex(pattern.to_owned()));
+        }
+
+        Ok(matcher)
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            Matcher::Glob(pattern) => pattern.matches(value),
+            Matcher::Regex(regex) => regex.is_match(value),
+        }
+    }
+
+    // Form understood by the C runtime
+    fn as_pattern(&self) -> String {
+        match self {
+            Matcher::Glob(pattern) => pattern.as_str().to_owned(),
+            Matcher::Regex(regex) => format!("{}{}", REGEX_PREFIX, regex.as_str()),
+        }
+    }
+}
+
+// Rejects the regex syntax POSIX ERE lacks or reads differently:
+// escapes like `\d` or `\b`, groups starting with `(?`, lazy
+// quantifiers and backslashes inside bracket expressions
+fn is_posix_ere(regex: &str) -> bool {
+    let mut chars = regex.chars().peekable();
+    let mut in_bracket = false;
+    let mut bracket_start = false;
+    let mut after_quantifier = false;
+
+    while let Some(c) = chars.next() {
+        if in_bracket {
+            match c {
+                '\\' => return false,
+                ']' if !bracket_start => in_bracket = false,
+                '[' if chars.peek().is_some_and(|n| matches!(n, ':' | '.' | '=')) => {
+                    // character class like [:alpha:], skipped whole
+                    let close = chars.next().unwrap_or(':');
+                    let mut prev = ' ';
+                    for n in chars.by_ref() {
+                        if prev == close && n == ']' {
+                            break;
+                        }
+                        prev = n;
+                    }
+                }
+                _ => {}
+            }
+            bracket_start = bracket_start && c == '^';
+            continue;
+        }
+
+        match c {
+            // the escaped character is consumed by the guard
+            '\\' if chars.next().is_none_or(|n| n.is_alphanumeric()) => return false,
+            '[' => {
+                in_bracket = true;
+                bracket_start = true;
+            }
+            '(' if chars.peek() == Some(&'?') => return false,
+            '?' if after_quantifier => return false,
+            _ => {}
+        }
+        after_quantifier = matches!(c, '*' | '+' | '?' | '}');
+    }
+
+    !in_bracket
+}
+
+/// Accepts values matching any include pattern (or every value without
+/// include patterns) that match none of the exclude patterns
+#[derive(Debug, Default, Clone)]
+pub struct Filter {
+    pub include: Vec<Matcher>,
+    pub exclude: Vec<Matcher>,
+}
+
+impl Filter {
+    pub fn accepts(&self, value: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|m| m.matches(value)))
+            && !self.exclude.iter().any(|m| m.matches(value))
+    }
+
+    /// Environment handed to children so the runtime applies
+    /// the same test name filter before registering tests
+    pub fn to_env(&self) -> Vec<(&'static str, String)> {
+        let join = |matchers: &[Matcher]| {
+            matchers
+                .iter()
+                .map(Matcher::as_pattern)
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        let mut env = Vec::new();
+        if !self.include.is_empty() {
+            env.push((TEST_FILTER_ENV, join(&self.include)));
+        }
+        if !self.exclude.is_empty() {
+            env.push((TEST_EXCLUDE_ENV, join(&self.exclude)));
+        }
+
+        env
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> Filter {
+        Filter {
+            include: include.iter().map(|p| Matcher::parse(p).unwrap()).collect(),
+            exclude: exclude.iter().map(|p| Matcher::parse(p).unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn accepts_glob_and_regex() {
+        let f = filter(&["bin/math/*", "re:^bin/io/read_.*$"], &["*_slow"]);
+
+        assert!(f.accepts("bin/math/add"));
+        assert!(f.accepts("bin/io/read_file"));
+        assert!(!f.accepts("bin/io/write_file"));
+        assert!(!f.accepts("bin/math/add_slow"));
+        assert!(Filter::default().accepts("anything"));
+    }
+
+    #[test]
+    fn env_keeps_regex_prefix() {
+        let f = filter(&["add*", "re:^mul"], &["empty"]);
+
+        assert_eq!(
+            f.to_env(),
+            vec![
+                (TEST_FILTER_ENV, "add*\nre:^mul".to_string()),
+                (TEST_EXCLUDE_ENV, "empty".to_string()),
+            ]
+        );
+        assert!(Matcher::parse("re:(").is_err());
+    }
+
+    #[test]
+    fn test_regex_limited_to_posix_ere() {
+        for pattern in ["re:^add_[0-9]+$", "re:(mul|div)\\.", "re:[[:alpha:]_]{2,}"] {
+            assert!(Matcher::parse_test(pattern).is_ok(), "{}", pattern);
+        }
+        for pattern in ["re:\\d+", "re:\\bmul", "re:(?i)add", "re:a.*?b", "re:[\\w]"] {
+            assert!(Matcher::parse_test(pattern).is_err(), "{}", pattern);
+        }
+    }
+}
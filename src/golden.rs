@@ -0,0 +1,135 @@
+use std::{fs, io, path::Path};
+
+use similar::TextDiff;
+
+pub const GOLDEN_TEST_NAME: &str = "golden_output";
+
+#[derive(Debug)]
+pub enum GoldenOutcome {
+    Matched,
+    Blessed,
+    MissingExpected,
+    Mismatch(String),
+}
+
+#[inline]
+pub fn expected_path(exe_path: &str) -> String {
+    format!("{}.expected", exe_path)
+}
+
+/// Compares the captured stdout of an executable against the `.expected`
+/// file next to it, with `bless` the expected file is replaced instead
+/// unless the executable failed, its output is then only compared
+pub fn compare_output(
+    exe_path: &str,
+    captured: &Path,
+    bless: bool,
+    exited_ok: bool,
+) -> io::Result<GoldenOutcome> {
+    let actual = fs::read_to_string(captured)?;
+    let expected_file = expected_path(exe_path);
+
+    if bless && exited_ok {
+        fs::write(&expected_file, actual)?;
+        return Ok(GoldenOutcome::Blessed);
+    }
+
+    let expected = match fs::read_to_string(&expected_file) {
+        Ok(expected) => expected,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(GoldenOutcome::MissingExpected);
+        }
+        Err(e) => return Err(e),
+    };
+
+    if expected == actual {
+        return Ok(GoldenOutcome::Matched);
+    }
+
+    let diff = TextDiff::from_lines(&expected, &actual)
+        .unified_diff()
+        .context_radius(3)
+        .header(&expected_file, "actual")
+        .to_string();
+
+    Ok(GoldenOutcome::Mismatch(diff))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    // executable path and captured stdout inside a fresh directory
+    fn golden_dir(name: &str, expected: Option<&str>, actual: &str) -> (PathBuf, String, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("golden-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let exe_path = dir.join("prog").to_string_lossy().into_owned();
+        if let Some(expected) = expected {
+            fs::write(expected_path(&exe_path), expected).unwrap();
+        }
+        let captured = dir.join("stdout.txt");
+        fs::write(&captured, actual).unwrap();
+
+        (dir, exe_path, captured)
+    }
+
+    #[test]
+    fn mismatch_renders_unified_diff() {
+        let (dir, exe_path, captured) = golden_dir("diff", Some("a\nb\n"), "a\nc\n");
+
+        let Ok(GoldenOutcome::Mismatch(diff)) = compare_output(&exe_path, &captured, false, true)
+        else {
+            panic!("expected a mismatch");
+        };
+        assert!(diff.starts_with(&format!("--- {}\n+++ actual\n", expected_path(&exe_path))));
+        assert!(diff.contains("\n a\n-b\n+c\n"));
+
+        fs::write(&captured, "a\nb\n").unwrap();
+        assert!(matches!(
+            compare_output(&exe_path, &captured, false, true),
+            Ok(GoldenOutcome::Matched)
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_expected_is_reported() {
+        let (dir, exe_path, captured) = golden_dir("missing", None, "a\n");
+
+        assert!(matches!(
+            compare_output(&exe_path, &captured, false, true),
+            Ok(GoldenOutcome::MissingExpected)
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bless_keeps_expected_output_of_failed_runs() {
+        let (dir, exe_path, captured) = golden_dir("bless", Some("old\n"), "partial\n");
+
+        assert!(matches!(
+            compare_output(&exe_path, &captured, true, false),
+            Ok(GoldenOutcome::Mismatch(_))
+        ));
+        assert_eq!(
+            fs::read_to_string(expected_path(&exe_path)).unwrap(),
+            "old\n"
+        );
+
+        assert!(matches!(
+            compare_output(&exe_path, &captured, true, true),
+            Ok(GoldenOutcome::Blessed)
+        ));
+        assert_eq!(
+            fs::read_to_string(expected_path(&exe_path)).unwrap(),
+            "partial\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    args::OutputFormat,
+    filter::Filter,
+    get_global_config_ref,
+    record_collection::{TestStatus, collection::CompiledRecord},
+};
+
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+// Runs shown in the status trail of a test
+const TRAIL_LENGTH: usize = 10;
+
+/// A single test result of a past run, one JSON object per line
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    // nanoseconds since the epoch and the pid of the runner,
+    // together shared by every entry of a run
+    pub run: u64,
+    pub pid: u32,
+    pub commit: Option<String>,
+    pub host: Option<String>,
+    pub program: String,
+    pub test: String,
+    pub status: TestStatus,
+    pub duration: Option<Duration>,
+}
+
+/// Aggregated history of one test, entries oldest first
+#[derive(Debug, Serialize)]
+pub struct TestHistory {
+    pub program: String,
+    pub test: String,
+    pub runs: usize,
+    pub passed: usize,
+    pub failed: usize,
+    // share of runs that were flaky or changed outcome from the previous run
+    pub flakiness: f64,
+    // mean duration of the older and the newer half of the runs
+    pub older_mean: Option<Duration>,
+    pub newer_mean: Option<Duration>,
+    pub trail: String,
+}
+
+/// Location of the history appended after every run
+pub fn default_history_path() -> PathBuf {
+    Path::new(&get_global_config_ref().path.report_out).join(HISTORY_FILE)
+}
+
+/// Appends every test of `record` to the history at `path`
+pub fn append_run(record: &CompiledRecord, path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let run = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let pid = std::process::id();
+    let commit = commit_hash();
+    let host = fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .map(|h| h.trim().to_owned());
+
+    let mut lines = String::new();
+    for (program, tests) in record.program_tests() {
+        for test in tests {
+            let Some((status, duration)) = record.test_result(program, test) else {
+                continue;
+            };
+
+            let entry = HistoryEntry {
+                run,
+                pid,
+                commit: commit.clone(),
+                host: host.clone(),
+                program: program.to_owned(),
+                test: test.to_owned(),
+                status,
+                duration,
+            };
+            lines.push_str(&serde_json::to_string(&entry).map_err(io::Error::other)?);
+            lines.push('\n');
+        }
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(lines.as_bytes())
+}
+
+pub fn read_history(path: &Path) -> io::Result<Vec<HistoryEntry>> {
+    let file = fs::File::open(path)?;
+    let mut entries = Vec::new();
+
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(
+            serde_json::from_str(&line)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        );
+    }
+
+    Ok(entries)
+}
+
+/// Groups the entries per test, keeping the programs and tests accepted by the filters
+pub fn summarize(
+    entries: &[HistoryEntry],
+    exe_filter: &Filter,
+    test_filter: &Filter,
+) -> Vec<TestHistory> {
+    let mut grouped: BTreeMap<(&str, &str), Vec<&HistoryEntry>> = BTreeMap::new();
+    for entry in entries {
+        if exe_filter.accepts(&entry.program) && test_filter.accepts(&entry.test) {
+            grouped
+                .entry((&entry.program, &entry.test))
+                .or_default()
+                .push(entry);
+        }
+    }
+
+    grouped
+        .into_iter()
+        .map(|((program, test), mut runs)| {
+            runs.sort_by_key(|entry| (entry.run, entry.pid));
+            summarize_test(program, test, &runs)
+        })
+        .collect()
+}
+
+fn summarize_test(program: &str, test: &str, runs: &[&HistoryEntry]) -> TestHistory {
+    let passed = runs.iter().filter(|e| e.status.passed()).count();
+
+    let unstable = runs
+        .iter()
+        .enumerate()
+        .filter(|(i, entry)| {
+            matches!(entry.status, TestStatus::Flaky)
+                || (*i > 0 && runs[i - 1].status.passed() != entry.status.passed())
+        })
+        .count();
+
+    let (older, newer) = runs.split_at(runs.len() / 2);
+
+    let trail = runs[runs.len().saturating_sub(TRAIL_LENGTH)..]
+        .iter()
+        .map(|entry| match entry.status {
+            TestStatus::Success => '.',
+            TestStatus::Flaky => '~',
+            TestStatus::Fail => 'F',
+            TestStatus::Crash => 'C',
+            TestStatus::SetupFail => 'S',
+            TestStatus::Interrupted => 'I',
+        })
+        .collect();
+
+    TestHistory {
+        program: program.to_owned(),
+        test: test.to_owned(),
+        runs: runs.len(),
+        passed,
+        failed: runs.len() - passed,
+        flakiness: unstable as f64 / runs.len() as f64,
+        older_mean: mean_duration(older),
+        newer_mean: mean_duration(newer),
+        trail,
+    }
+}
+
+fn mean_duration(runs: &[&HistoryEntry]) -> Option<Duration> {
+    let durations: Vec<Duration> = runs.iter().filter_map(|e| e.duration).collect();
+    if durations.is_empty() {
+        return None;
+    }
+
+    Some(durations.iter().sum::<Duration>() / durations.len() as u32)
+}
+
+pub fn format_history(histories: &[TestHistory], format: OutputFormat) -> io::Result<String> {
+    if let OutputFormat::Json = format {
+        return serde_json::to_string_pretty(histories).map_err(io::Error::other);
+    }
+
+    let mut out = String::new();
+    for h in histories {
+        let trend = match (h.older_mean, h.newer_mean) {
+            (Some(older), Some(newer)) => format!("{:?} -> {:?}", older, newer),
+            (None, Some(newer)) => format!("{:?}", newer),
+            _ => "-".to_owned(),
+        };
+
+        out.push_str(&format!(
+            "{} :: {}\n\truns: {}\tpassed: {}\tfailed: {}\tflakiness: {:.0}%\n\tduration: {}\tlast: {}\n",
+            h.program,
+            h.test,
+            h.runs,
+            h.passed,
+            h.failed,
+            h.flakiness * 100.0,
+            trend,
+            h.trail
+        ));
+    }
+
+    Ok(out)
+}
+
+// Commit of the working directory, absent outside a git repository
+fn commit_hash() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(run: u64, status: TestStatus, millis: u64) -> HistoryEntry {
+        HistoryEntry {
+            run,
+            pid: 0,
+            commit: None,
+            host: None,
+            program: "bin/a".to_owned(),
+            test: "add".to_owned(),
+            status,
+            duration: Some(Duration::from_millis(millis)),
+        }
+    }
+
+    #[test]
+    fn summary_counts_flips_and_trend() {
+        let entries = vec![
+            entry(3, TestStatus::Fail, 30),
+            entry(1, TestStatus::Success, 10),
+            entry(2, TestStatus::Success, 10),
+            entry(4, TestStatus::Success, 30),
+        ];
+
+        let summary = summarize(&entries, &Filter::default(), &Filter::default());
+        assert_eq!(summary.len(), 1);
+
+        let add = &summary[0];
+        assert_eq!((add.runs, add.passed, add.failed), (4, 3, 1));
+        assert_eq!(add.flakiness, 0.5);
+        assert_eq!(add.older_mean, Some(Duration::from_millis(10)));
+        assert_eq!(add.newer_mean, Some(Duration::from_millis(30)));
+        assert_eq!(add.trail, "..F.");
+    }
+}
@@ -0,0 +1,81 @@
+use serde::Serialize;
+
+use crate::{args::OutputFormat, collect::FileCollection, spawner::spawn_executable};
+
+#[derive(Debug, Serialize)]
+pub struct ListedProgram {
+    pub program: String,
+    pub tests: Vec<String>,
+}
+
+/// Asks every executable for its tests, runtime.h executables answer
+/// the hello frame and register their tests without running them.
+/// Other executables are stopped and listed with their exit code test
+pub fn list_tests(fc: FileCollection) -> Vec<ListedProgram> {
+    let mut listed = Vec::new();
+
+    if let Some(record) = spawn_executable(fc) {
+        listed.extend(
+            record
+                .program_tests()
+                .into_iter()
+                .map(|(program, tests)| ListedProgram {
+                    program: program.to_owned(),
+                    tests: tests.into_iter().map(str::to_owned).collect(),
+                }),
+        );
+    }
+
+    listed.sort_by(|a, b| a.program.cmp(&b.program));
+    listed
+}
+
+pub fn format_listing(listed: &[ListedProgram], format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(listed).unwrap_or_default() + "\n",
+        OutputFormat::Text => listed
+            .iter()
+            .map(|p| {
+                let tests: String = p.tests.iter().map(|t| format!("    {}\n", t)).collect();
+                format!("{}\n{}", p.program, tests)
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn listed() -> Vec<ListedProgram> {
+        vec![
+            ListedProgram {
+                program: "bin/a".to_owned(),
+                tests: vec!["add".to_owned(), "mul".to_owned()],
+            },
+            ListedProgram {
+                program: "bin/legacy".to_owned(),
+                tests: vec!["exit_code".to_owned()],
+            },
+        ]
+    }
+
+    #[test]
+    fn text_listing_indents_tests_under_their_program() {
+        assert_eq!(
+            format_listing(&listed(), OutputFormat::Text),
+            "bin/a\n    add\n    mul\nbin/legacy\n    exit_code\n"
+        );
+        assert_eq!(format_listing(&[], OutputFormat::Text), "");
+    }
+
+    #[test]
+    fn json_listing_keeps_program_and_tests() {
+        let json = format_listing(&listed(), OutputFormat::Json);
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed[0]["program"], "bin/a");
+        assert_eq!(parsed[0]["tests"], serde_json::json!(["add", "mul"]));
+        assert_eq!(parsed[1]["tests"], serde_json::json!(["exit_code"]));
+    }
+}
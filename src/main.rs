@@ -1,31 +1,68 @@
+// Progress lines of the runner go to stderr while listing,
+// so stdout only holds the test list
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if $crate::listing() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+mod args;
+mod build;
 mod collect;
+mod configs;
+mod core_dump;
+mod coverage;
 mod displayer;
+mod filter;
+mod golden;
+mod history;
+mod list;
+mod memcheck;
 mod record_collection;
+mod regression;
+mod report;
+mod retry;
+mod sanitizer;
+mod shard;
 mod spawner;
 mod util;
+mod watch;
 
+use std::collections::HashSet;
 use std::fs;
+use std::path::Path;
 use std::sync::OnceLock;
 
-use spawner::spawn_executable;
 use termion::color;
 
 use crate::{
+    args::{Args, Command},
     collect::{CollectErr, FileCollection},
     configs::{Config, TargetConfig},
+    record_collection::{TestStatus, collection::CompiledRecord},
 };
 
-const DEFAULT_SOURCE: &str = "bin_test";
-
 static CONFIG_VARS: OnceLock<Config> = OnceLock::new();
+static ARGS_VARS: OnceLock<Args> = OnceLock::new();
+
+fn args_init() {
+    let args = Args::parse(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("Invalid arguments: {}", e);
+        std::process::exit(1);
+    });
+
+    ARGS_VARS
+        .set(args)
+        .expect("Global Args Variable is already initialized!");
+}
 
 fn config_init() {
     const SOURCE_CONFIG: &str = "config.toml";
 
-    // Read any argument eing passed
-    // let args: Vec<String> = std::env::args().collect();
-    // println!("Passed Arguments: {:?}", args);
-
     CONFIG_VARS
         .set({
             let setting_toml_str =
@@ -34,7 +71,7 @@ fn config_init() {
             let setting =
                 toml::from_str::<TargetConfig>(&setting_toml_str).expect("Unable to parse config!");
 
-            println!("{:?}", setting);
+            progress!("{:?}", setting);
 
             let target_toml_str =
                 fs::read_to_string(setting.setting.config_path.to_owned() + "/config.toml")
@@ -53,92 +90,303 @@ fn get_global_config_ref() -> &'static Config {
     CONFIG_VARS.get().expect("Uninitialized Global Config")
 }
 
+#[inline(always)]
+fn get_global_args_ref() -> &'static Args {
+    ARGS_VARS.get().expect("Uninitialized Global Args")
+}
+
+#[inline]
+fn listing() -> bool {
+    ARGS_VARS.get().is_some_and(|args| args.command == Command::List)
+}
+
 fn locate_bin_files() -> Result<FileCollection, CollectErr> {
     // Do some matching for configurations
     let target = get_global_config_ref().target_config.bin_target.as_str();
 
-    println!(
+    progress!(
         "{}[ Collecting Compiled Test in {}... ]{}",
         color::Fg(color::Yellow),
         target,
         color::Fg(color::Reset)
     );
 
-    collect::collect_test_files(target)
+    collect::collect_test_files(target, &get_global_args_ref().exe_filter)
 }
 
-fn main() {
-    // Initialize static variables
-    config_init();
+// Keeps the executables of the requested shard
+fn apply_shard(file_collection: &mut FileCollection) {
+    let args = get_global_args_ref();
+    let Some(shard) = args.shard else {
+        return;
+    };
 
-    let file_collection = locate_bin_files().unwrap_or_else(|e| {
-        eprintln!("There was a problem collecting files: {:?}", e);
-        std::process::exit(1);
+    let timings = args.shard_timings.as_ref().map(|path| {
+        report::read_report(Path::new(path))
+            .unwrap_or_else(|e| {
+                eprintln!("Unable to read shard timings from {}: {}", path, e);
+                std::process::exit(1);
+            })
+            .program_durations()
     });
 
-    // println!("{:?}\n", file_collection);
+    let paths: Vec<&str> = file_collection
+        .exe_info
+        .iter()
+        .map(|(_, path)| path.as_str())
+        .collect();
+    let selected: HashSet<String> = shard
+        .assign(&paths, timings.as_ref())
+        .into_iter()
+        .map(str::to_owned)
+        .collect();
+
+    file_collection.retain(|path| selected.contains(path));
+
     println!(
-        "{}[ Setting up executables... ]{}",
+        "{}[ Shard {}/{}: {} executables ]{}",
         color::Fg(color::Yellow),
+        shard.index,
+        shard.count,
+        file_collection.len(),
         color::Fg(color::Reset)
     );
-    
-    
-    let collection = spawn_executable(file_collection);
-    match collection {
-        Some(c) => println!("{:#?}", c),
-        None => println!("--- Collection is Empty! ---"),
-    } 
+}
+
+// Narrows the collection down to the failures of the last report
+fn apply_rerun_failed(file_collection: &mut FileCollection) {
+    if !get_global_args_ref().rerun_failed {
+        return;
+    }
+
+    let report_path = report::default_report_path();
+    let failed = report::read_report(&report_path)
+        .unwrap_or_else(|e| {
+            eprintln!(
+                "Unable to read last report {}: {}",
+                report_path.display(),
+                e
+            );
+            std::process::exit(1);
+        })
+        .failed_tests();
+
+    file_collection.retain(|path| failed.contains_key(path));
+    file_collection.test_names = failed
+        .into_iter()
+        .filter(|(_, tests)| !tests.is_empty())
+        .collect();
 
     println!(
-        "{}[ Finished Executing ]{}",
-        color::Fg(color::Green),
+        "{}[ Rerunning {} failed executables ]{}",
+        color::Fg(color::Yellow),
+        file_collection.len(),
         color::Fg(color::Reset)
     );
 }
 
-mod configs {
-    use serde::Deserialize;
+// Flags tests and executables slower than in the `--baseline` report
+fn check_baseline(record: &mut CompiledRecord) {
+    let Some(path) = get_global_args_ref().baseline.as_ref() else {
+        return;
+    };
+
+    let baseline = report::read_report(Path::new(path)).unwrap_or_else(|e| {
+        eprintln!("Unable to read baseline {}: {}", path, e);
+        std::process::exit(1);
+    });
 
-    #[derive(Debug, Deserialize)]
-    pub struct TargetConfig {
-        pub setting: TargetPath,
+    let config = &get_global_config_ref().regression;
+    let regressions = regression::find_regressions(&baseline, record, config);
+    regression::apply_regressions(record, &regressions, config);
+}
+
+// Prints the results of a run and appends them to the history
+fn finish_run(record: &mut CompiledRecord) {
+    check_baseline(record);
+    print!("{}", record);
+
+    for program in record.build_failures().keys() {
+        println!(
+            "{}[ Not Built: {} ]{}",
+            color::Fg(color::Red),
+            program,
+            color::Fg(color::Reset)
+        );
     }
 
-    #[derive(Debug, Deserialize, Clone)]
-    pub struct TargetPath {
-        pub config_path: String,
-        pub bin_target: String,
+    for (program, tests) in record.tests_with_status(TestStatus::SetupFail) {
+        for test in tests {
+            println!(
+                "{}[ Setup Failed: {} :: {} ]{}",
+                color::Fg(color::Red),
+                program,
+                test,
+                color::Fg(color::Reset)
+            );
+        }
     }
 
-    impl Default for TargetPath {
-        fn default() -> Self {
-            Self {
-                config_path: Default::default(),
-                bin_target: Default::default(),
+    for (program, diagnostics) in record.diagnostics() {
+        for diagnostic in diagnostics {
+            println!(
+                "{}[ {}: {} - {} :: {} ]{}",
+                color::Fg(color::Red),
+                diagnostic.tool,
+                diagnostic.message,
+                program,
+                diagnostic.test.as_deref().unwrap_or("*"),
+                color::Fg(color::Reset)
+            );
+            for frame in diagnostic.frames.iter() {
+                println!(
+                    "\t{} {}",
+                    frame.function.as_deref().unwrap_or("??"),
+                    frame.location
+                );
             }
         }
     }
 
-    #[derive(Debug, Deserialize)]
-    pub struct Config {
-        #[serde(skip_deserializing)]
-        pub target_config: TargetPath,
-        pub path: ConfigPath,
-        pub process: ConfigWorker,
+    let config = get_global_config_ref();
+    if config.coverage.enabled
+        && let Err(e) = coverage::report_coverage(&config.coverage)
+    {
+        eprintln!("Unable to write coverage report: {}", e);
+    }
+
+    let history_path = history::default_history_path();
+    if let Err(e) = history::append_run(record, &history_path) {
+        eprintln!("Unable to append history {}: {}", history_path.display(), e);
+    }
+}
+
+fn save_report(record: &CompiledRecord) {
+    let report_path = report::default_report_path();
+    if let Err(e) = report::write_report(record, &report_path) {
+        eprintln!("Unable to write report {}: {}", report_path.display(), e);
     }
+}
 
-    // report out used for outputting test reports
-    // error out are for internal error within the TestRunner
-    #[derive(Debug, Deserialize)]
-    pub struct ConfigPath {
-        pub report_out: String,
-        pub error_out: String,
+fn merge_reports() {
+    let args = get_global_args_ref();
+    if args.inputs.is_empty() {
+        eprintln!("merge expects at least one report");
+        std::process::exit(1);
     }
 
-    #[derive(Debug, Deserialize, Clone)]
-    pub struct ConfigWorker {
-        pub max_child_spawn: usize,
-        pub worker_count: usize,
+    let merged = report::merge_reports(&args.inputs)
+        .and_then(|record| report::format_report(&record, args.format))
+        .and_then(|content| util::write_output(args.output.as_deref(), &content));
+
+    if let Err(e) = merged {
+        eprintln!("Unable to merge reports: {}", e);
+        std::process::exit(1);
     }
 }
+
+fn show_history() {
+    let args = get_global_args_ref();
+    let history_path = history::default_history_path();
+
+    let shown = history::read_history(&history_path)
+        .map(|entries| history::summarize(&entries, &args.exe_filter, &args.test_filter))
+        .and_then(|histories| history::format_history(&histories, args.format))
+        .and_then(|content| util::write_output(args.output.as_deref(), &content));
+
+    if let Err(e) = shown {
+        eprintln!(
+            "Unable to show history {}: {}",
+            history_path.display(),
+            e
+        );
+        std::process::exit(1);
+    }
+}
+
+fn main() {
+    // Initialize static variables
+    args_init();
+
+    // merging only reads the given reports
+    if get_global_args_ref().command == Command::Merge {
+        merge_reports();
+        return;
+    }
+
+    config_init();
+
+    if get_global_args_ref().command == Command::History {
+        show_history();
+        return;
+    }
+
+    let build_failures = match get_global_config_ref().build.as_ref() {
+        Some(build) if get_global_args_ref().command != Command::List => {
+            build::build_sources(build)
+        }
+        _ => Vec::new(),
+    };
+
+    let mut file_collection = locate_bin_files().unwrap_or_else(|e| {
+        eprintln!("There was a problem collecting files: {:?}", e);
+        std::process::exit(1);
+    });
+
+    apply_rerun_failed(&mut file_collection);
+    apply_shard(&mut file_collection);
+
+    if get_global_args_ref().command == Command::List {
+        let args = get_global_args_ref();
+        let listed = list::list_tests(file_collection);
+
+        util::write_output(
+            args.output.as_deref(),
+            &list::format_listing(&listed, args.format),
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("Unable to write test list: {}", e);
+            std::process::exit(1);
+        });
+        return;
+    }
+
+    // println!("{:?}\n", file_collection);
+    println!(
+        "{}[ Setting up executables... ]{}",
+        color::Fg(color::Yellow),
+        color::Fg(color::Reset)
+    );
+
+    if get_global_config_ref().coverage.enabled
+        && let Err(e) = coverage::reset_data()
+    {
+        eprintln!("Unable to remove previous coverage data: {}", e);
+    }
+
+    let mut collection = retry::run_with_retries(file_collection);
+    if !build_failures.is_empty() {
+        let record = collection.get_or_insert_with(CompiledRecord::empty);
+        for (program, output) in build_failures {
+            record.add_build_failure(program, output);
+        }
+    }
+
+    match collection.as_mut() {
+        Some(c) => {
+            finish_run(c);
+            save_report(c);
+        }
+        None => println!("--- Collection is Empty! ---"),
+    }
+
+    if get_global_args_ref().command == Command::Watch {
+        watch::watch_bin_target(collection.unwrap_or_else(CompiledRecord::empty));
+    }
+
+    println!(
+        "{}[ Finished Executing ]{}",
+        color::Fg(color::Green),
+        color::Fg(color::Reset)
+    );
+}
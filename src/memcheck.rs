@@ -0,0 +1,139 @@
+use roxmltree::{Document, Node};
+
+use crate::record_collection::{Diagnostic, StackFrame};
+
+/// File inside the scratch directory receiving the memcheck XML
+pub const MEMCHECK_XML: &str = "memcheck.xml";
+
+/// Arguments making valgrind write its XML report to `xml_file`
+pub fn xml_args(xml_file: &str) -> [String; 2] {
+    ["--xml=yes".to_owned(), format!("--xml-file={}", xml_file)]
+}
+
+/// Diagnostic standing for a memcheck report that could not be read,
+/// so the wrapped executable is not counted as clean
+pub fn unreadable_report(reason: String) -> Diagnostic {
+    Diagnostic {
+        tool: "Memcheck".to_owned(),
+        kind: "UnreadableReport".to_owned(),
+        message: reason,
+        test: None,
+        frames: Vec::new(),
+    }
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|child| child.has_tag_name(name))
+        .and_then(|child| child.text())
+}
+
+fn frame(node: Node) -> StackFrame {
+    let location = match (child_text(node, "dir"), child_text(node, "file")) {
+        (dir, Some(file)) => {
+            let path = dir.map_or(file.to_owned(), |dir| format!("{}/{}", dir, file));
+            match child_text(node, "line") {
+                Some(line) => format!("{}:{}", path, line),
+                None => path,
+            }
+        }
+        _ => child_text(node, "obj").unwrap_or("??").to_owned(),
+    };
+
+    StackFrame {
+        function: child_text(node, "fn").map(str::to_owned),
+        location,
+    }
+}
+
+/// Errors of a memcheck XML report, each with the first `max_frames`
+/// frames of its stack
+pub fn parse_memcheck(xml: &str, max_frames: usize) -> Result<Vec<Diagnostic>, roxmltree::Error> {
+    let document = Document::parse(xml)?;
+
+    let diagnostics = document
+        .descendants()
+        .filter(|node| node.has_tag_name("error"))
+        .map(|error| {
+            // leaks describe themselves in <xwhat><text>
+            let message = child_text(error, "what")
+                .or_else(|| {
+                    error
+                        .children()
+                        .find(|child| child.has_tag_name("xwhat"))
+                        .and_then(|xwhat| child_text(xwhat, "text"))
+                })
+                .unwrap_or_default();
+
+            let frames = error
+                .children()
+                .find(|child| child.has_tag_name("stack"))
+                .map(|stack| {
+                    stack
+                        .children()
+                        .filter(|child| child.has_tag_name("frame"))
+                        .take(max_frames)
+                        .map(frame)
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            Diagnostic {
+                tool: "Memcheck".to_owned(),
+                kind: child_text(error, "kind").unwrap_or("Unknown").to_owned(),
+                message: message.to_owned(),
+                test: None,
+                frames,
+            }
+        })
+        .collect();
+
+    Ok(diagnostics)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_invalid_write_and_leak() {
+        let xml = r#"<?xml version="1.0"?>
+<valgrindoutput>
+  <error>
+    <unique>0x0</unique>
+    <kind>InvalidWrite</kind>
+    <what>Invalid write of size 4</what>
+    <stack>
+      <frame><ip>0x1</ip><obj>/tmp/bt/mem</obj><fn>overflow</fn><dir>/tmp/src</dir><file>mem.c</file><line>10</line></frame>
+      <frame><ip>0x2</ip><obj>/usr/lib/libc.so.6</obj></frame>
+    </stack>
+  </error>
+  <error>
+    <unique>0x1</unique>
+    <kind>Leak_DefinitelyLost</kind>
+    <xwhat><text>16 bytes in 1 blocks are definitely lost</text><leakedbytes>16</leakedbytes></xwhat>
+    <stack>
+      <frame><ip>0x3</ip><fn>malloc</fn></frame>
+    </stack>
+  </error>
+</valgrindoutput>"#;
+
+        let diagnostics = parse_memcheck(xml, 5).unwrap();
+        assert_eq!(diagnostics.len(), 2);
+
+        assert_eq!(diagnostics[0].kind, "InvalidWrite");
+        assert_eq!(diagnostics[0].message, "Invalid write of size 4");
+        assert_eq!(
+            diagnostics[0].frames[0].function.as_deref(),
+            Some("overflow")
+        );
+        assert_eq!(diagnostics[0].frames[0].location, "/tmp/src/mem.c:10");
+        assert_eq!(diagnostics[0].frames[1].location, "/usr/lib/libc.so.6");
+
+        assert_eq!(diagnostics[1].kind, "Leak_DefinitelyLost");
+        assert_eq!(
+            diagnostics[1].message,
+            "16 bytes in 1 blocks are definitely lost"
+        );
+    }
+}
@@ -1,17 +1,39 @@
 use std::{
-    collections::{BTreeMap, HashMap}, fmt::Display, ops::{Index, IndexMut}, sync::{Arc, Mutex, RwLock}
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    ops::{Index, IndexMut},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
 };
 
+use serde::{Deserialize, Serialize};
 use termion::color;
 
 use super::ProgramInfoType;
-use super::{LogTypeMessage, RecordErr, StatusType};
+use super::{Attempt, Diagnostic, LogTypeMessage, ProcessExit, ProgramRun, RecordErr, TestStatus};
 use crate::{
-    record_collection::{Log, ProcessInfo, Register, Status},
+    golden::GOLDEN_TEST_NAME,
+    record_collection::{ProcessInfo, Register, Status},
+    regression::DURATION_TEST_NAME,
     util,
 };
 
 type Logs = Vec<LogTypeMessage>;
+
+/// Test standing in for executables that never registered a test,
+/// its status is taken from the exit code
+pub const EXIT_CODE_TEST_NAME: &str = "exit_code";
+
+/// Test failing a program for diagnostics no test of its stack can be blamed for
+pub const DIAGNOSTICS_TEST_NAME: &str = "diagnostics";
+
+// Tests evaluated by the runner rather than started by the runtime
+const RUNNER_TEST_NAMES: [&str; 4] = [
+    EXIT_CODE_TEST_NAME,
+    GOLDEN_TEST_NAME,
+    DURATION_TEST_NAME,
+    DIAGNOSTICS_TEST_NAME,
+];
 type TestKeys = RwLock<HashMap<String, usize>>;
 
 pub trait StoreData {
@@ -21,23 +43,45 @@ pub trait StoreData {
     fn store(&self, data: Self::T) -> Result<(), Self::U>;
 }
 
-
 #[derive(Debug)]
 struct TestCollection {
     test_map: RwLock<BTreeMap<String, TestKeys>>,
-    test_status: Mutex<Vec<StatusType>>,
-    test_logs: RwLock<Vec<Mutex<Option<Logs>>>>
+    test_status: Mutex<Vec<TestStatus>>,
+    test_logs: RwLock<Vec<Mutex<Option<Logs>>>>,
+    test_durations: Mutex<Vec<Option<Duration>>>,
+    program_runs: Mutex<BTreeMap<String, ProgramRun>>,
+    protocol_programs: Mutex<HashSet<String>>,
+    // programs that sent the hello frame, i.e. built with runtime.h
+    hello_programs: Mutex<HashSet<String>>,
+    diagnostics: Mutex<BTreeMap<String, Vec<Diagnostic>>>,
 }
 
-
 #[derive(Debug)]
 pub struct TestRecord(Arc<TestCollection>);
 
-#[derive(Debug)]
-pub struct CompiledRecord{
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompiledRecord {
     test_tree: BTreeMap<String, HashMap<String, usize>>,
-    test_status: Vec<StatusType>,
-    test_logs: Vec<Option<Vec<LogTypeMessage>>>
+    test_status: Vec<TestStatus>,
+    test_logs: Vec<Option<Vec<LogTypeMessage>>>,
+    test_durations: Vec<Option<Duration>>,
+    // every attempt of retried tests, empty for tests that ran once
+    #[serde(default)]
+    test_attempts: Vec<Vec<Attempt>>,
+    program_runs: BTreeMap<String, ProgramRun>,
+    // earlier runs of programs started again by a retry,
+    // the latest run is the one in program_runs
+    #[serde(default)]
+    program_attempts: BTreeMap<String, Vec<ProgramRun>>,
+    // warnings about a whole program, e.g. a duration regression
+    #[serde(default)]
+    program_logs: BTreeMap<String, Vec<LogTypeMessage>>,
+    // compiler output of programs the build stage failed to compile
+    #[serde(default)]
+    build_failures: BTreeMap<String, String>,
+    // errors reported by tools inspecting the runs, per program
+    #[serde(default)]
+    diagnostics: BTreeMap<String, Vec<Diagnostic>>,
 }
 
 impl TestRecord {
@@ -46,56 +90,151 @@ impl TestRecord {
             test_map: RwLock::new(BTreeMap::new()),
             test_status: Mutex::new(Vec::new()),
             test_logs: RwLock::new(Vec::new()),
+            test_durations: Mutex::new(Vec::new()),
+            program_runs: Mutex::new(BTreeMap::new()),
+            protocol_programs: Mutex::new(HashSet::new()),
+            hello_programs: Mutex::new(HashSet::new()),
+            diagnostics: Mutex::new(BTreeMap::new()),
         }))
     }
 
     pub fn compile(self) -> Result<CompiledRecord, ()> {
-        let s = Arc::into_inner(self.0)
-            .ok_or(())?;
+        let s = Arc::into_inner(self.0).ok_or(())?;
 
-        let test_tree = s.test_map.into_inner()
+        let test_tree = s
+            .test_map
+            .into_inner()
             .map_err(|_| ())?
             .into_iter()
             .map(|(k, rwlock_map)| -> Result<_, ()> {
-                let v = rwlock_map.into_inner()
-                    .map_err(|_| ())?;
+                let v = rwlock_map.into_inner().map_err(|_| ())?;
 
-                Ok((k,v))
+                Ok((k, v))
             })
             .filter_map(Result::ok)
             .collect();
 
-
-        let test_status = s.test_status.into_inner()
+        let test_status = s
+            .test_status
+            .into_inner()
             .map_err(|_| ())?
             .into_iter()
             .collect();
 
-
-        let test_logs = s.test_logs.into_inner()
+        let test_logs = s
+            .test_logs
+            .into_inner()
             .map_err(|_| ())?
             .into_iter()
             .map(Mutex::into_inner)
             .filter_map(Result::ok)
             .collect();
 
+        let test_durations = s.test_durations.into_inner().map_err(|_| ())?;
+
+        let program_runs = s.program_runs.into_inner().map_err(|_| ())?;
+
+        let protocol_programs = s.protocol_programs.into_inner().map_err(|_| ())?;
+
+        let diagnostics = s.diagnostics.into_inner().map_err(|_| ())?;
 
-        Ok(CompiledRecord { 
+        let test_attempts = vec![Vec::new(); test_durations.len()];
+
+        let mut compiled = CompiledRecord {
             test_tree,
-            test_status, 
-            test_logs 
-        })
+            test_status,
+            test_logs,
+            test_durations,
+            test_attempts,
+            program_runs,
+            program_attempts: BTreeMap::new(),
+            program_logs: BTreeMap::new(),
+            build_failures: BTreeMap::new(),
+            diagnostics: BTreeMap::new(),
+        };
+
+        compiled.insert_exit_code_tests(&protocol_programs);
+        for (program, diagnostics) in diagnostics {
+            compiled.add_diagnostics(&program, diagnostics);
+        }
 
+        Ok(compiled)
     }
 
-        fn new_test_entry(&self) -> Option<usize> {
+    /// Leaves the program out of the exit code tests, e.g. when
+    /// started with a test filter the exit code test does not match
+    pub fn skip_exit_code_test(&self, program_name: &str) -> Result<(), RecordErr> {
+        self.0
+            .protocol_programs
+            .lock()
+            .map_err(|_| RecordErr::PoisonedLock)?
+            .insert(program_name.to_owned());
+
+        Ok(())
+    }
+
+    /// Keeps the errors a tool reported for a finished program
+    pub fn record_diagnostics(
+        &self,
+        program_name: &str,
+        diagnostics: Vec<Diagnostic>,
+    ) -> Result<(), RecordErr> {
+        if diagnostics.is_empty() {
+            return Ok(());
+        }
+
+        self.0
+            .diagnostics
+            .lock()
+            .map_err(|_| RecordErr::PoisonedLock)?
+            .entry(program_name.to_owned())
+            .or_default()
+            .extend(diagnostics);
+
+        Ok(())
+    }
+
+    pub fn record_exit(
+        &self,
+        program_name: &str,
+        exit: ProcessExit,
+        duration: Duration,
+    ) -> Result<(), RecordErr> {
+        self.0
+            .program_runs
+            .lock()
+            .map_err(|_| RecordErr::PoisonedLock)?
+            .insert(program_name.to_owned(), ProgramRun { exit, duration });
+
+        Ok(())
+    }
+
+    /// Whether a test of the program did not pass, tests
+    /// still waiting for their status count as failed
+    pub fn program_failed(&self, program_name: &str) -> bool {
+        let Ok(test_map) = self.0.test_map.read() else {
+            return true;
+        };
+        let Some(Ok(tests)) = test_map.get(program_name).map(RwLock::read) else {
+            return false;
+        };
+        let Ok(status) = self.0.test_status.lock() else {
+            return true;
+        };
+
+        tests
+            .values()
+            .any(|index| status.get(*index) != Some(&TestStatus::Success))
+    }
+
+    fn new_test_entry(&self) -> Option<usize> {
         let Ok(mut write_list_stat) = self.0.test_status.lock() else {
             return None;
         };
 
         let index = write_list_stat.len();
 
-        write_list_stat.push(StatusType::Fail);
+        write_list_stat.push(TestStatus::Fail);
         drop(write_list_stat);
 
         let Ok(mut write_list_logs) = self.0.test_logs.write() else {
@@ -103,7 +242,13 @@ impl TestRecord {
         };
 
         write_list_logs.push(Mutex::new(None));
+        drop(write_list_logs);
+
+        let Ok(mut write_list_durations) = self.0.test_durations.lock() else {
+            return None;
+        };
 
+        write_list_durations.push(None);
 
         Some(index)
     }
@@ -120,6 +265,52 @@ impl TestRecord {
         Ok(())
     }
 
+    // Adds a test that was evaluated by the runner itself
+    // rather than reported by the executable
+    pub fn insert_test(
+        &self,
+        program_name: &str,
+        test_name: &str,
+        status: TestStatus,
+        logs: Vec<LogTypeMessage>,
+    ) -> Result<(), RecordErr> {
+        let c = &self
+            .0
+            .test_map
+            .read()
+            .map_err(|_| RecordErr::PoisonedRead)?;
+
+        let mut process_data = c
+            .get(program_name)
+            .ok_or(RecordErr::ProgramNotExist)?
+            .write()
+            .map_err(|_| RecordErr::PoisonedWrite)?;
+
+        let entry_index = self.new_test_entry().ok_or(RecordErr::PoisonedLock)?;
+
+        *self
+            .0
+            .test_status
+            .lock()
+            .map_err(|_| RecordErr::PoisonedLock)?
+            .index_mut(entry_index) = status;
+
+        if !logs.is_empty() {
+            *self
+                .0
+                .test_logs
+                .read()
+                .map_err(|_| RecordErr::PoisonedRead)?
+                .index(entry_index)
+                .lock()
+                .map_err(|_| RecordErr::PoisonedLock)? = Some(logs);
+        }
+
+        process_data.insert(test_name.to_owned(), entry_index);
+
+        Ok(())
+    }
+
     fn register_test(&self, test_data: Register) -> Result<(), RecordErr> {
         let c = &self
             .0
@@ -149,6 +340,34 @@ impl TestRecord {
         Ok(())
     }
 
+    // Programs that sent the hello frame report their own
+    // tests and are not judged by their exit code
+    fn mark_protocol_program(&self, program_name: &[u8]) -> Result<(), RecordErr> {
+        let program_name = util::bytes_to_trimmed_string(program_name)
+            .map_err(|_| RecordErr::Utf8ConvertionErr)?;
+
+        self.0
+            .protocol_programs
+            .lock()
+            .map_err(|_| RecordErr::PoisonedLock)?
+            .insert(program_name.trim().to_owned());
+        self.0
+            .hello_programs
+            .lock()
+            .map_err(|_| RecordErr::PoisonedLock)?
+            .insert(program_name.trim().to_owned());
+
+        Ok(())
+    }
+
+    /// Whether the program sent the hello frame of the runtime protocol
+    pub fn sent_hello(&self, program_name: &str) -> bool {
+        self.0
+            .hello_programs
+            .lock()
+            .is_ok_and(|programs| programs.contains(program_name))
+    }
+
     fn update_test_status(&self, stat: Status) -> Result<(), RecordErr> {
         let test_map = &self
             .0
@@ -183,13 +402,25 @@ impl TestRecord {
 
         let test_ref = mutex_vec.index_mut(test_index);
 
-        *test_ref = stat.t;
+        *test_ref = stat.t.into();
+        drop(mutex_vec);
+
+        *self
+            .0
+            .test_durations
+            .lock()
+            .map_err(|_| RecordErr::PoisonedLock)?
+            .index_mut(test_index) = Some(Duration::from_nanos(stat.duration_ns));
 
         Ok(())
     }
 
-
-    fn append_test_logs(&self, log: Log) -> Result<(), RecordErr> {
+    fn append_test_logs(
+        &self,
+        program_name: &[u8],
+        function_name: &[u8],
+        message: LogTypeMessage,
+    ) -> Result<(), RecordErr> {
         let test_map = &self
             .0
             .test_map
@@ -198,9 +429,9 @@ impl TestRecord {
 
         let (program_name, function_name) = {
             (
-                util::bytes_to_trimmed_string(&log.program_name)
+                util::bytes_to_trimmed_string(program_name)
                     .map_err(|_| RecordErr::Utf8ConvertionErr)?,
-                util::bytes_to_trimmed_string(&log.function_name)
+                util::bytes_to_trimmed_string(function_name)
                     .map_err(|_| RecordErr::Utf8ConvertionErr)?,
             )
         };
@@ -220,21 +451,466 @@ impl TestRecord {
             .map_err(|_| RecordErr::PoisonedRead)?
             .index(test_index)
             .lock()
-            .map(|mut m|{
-
+            .map(|mut m| {
                 if let Some(v) = m.as_mut() {
-                    v.push(log.into());
+                    v.push(message);
                 } else {
-                    *m = Some(vec![log.into()])
+                    *m = Some(vec![message])
                 }
- 
             })
             .map_err(|_| RecordErr::PoisonedLock)?;
-            
+
         Ok(())
     }
 }
 
+impl CompiledRecord {
+    pub fn empty() -> Self {
+        Self {
+            test_tree: BTreeMap::new(),
+            test_status: Vec::new(),
+            test_logs: Vec::new(),
+            test_durations: Vec::new(),
+            test_attempts: Vec::new(),
+            program_runs: BTreeMap::new(),
+            program_attempts: BTreeMap::new(),
+            program_logs: BTreeMap::new(),
+            build_failures: BTreeMap::new(),
+            diagnostics: BTreeMap::new(),
+        }
+    }
+
+    /// Checks that a deserialized report only refers to tests it holds,
+    /// older reports without attempts get empty ones
+    pub fn validate(mut self) -> Result<Self, String> {
+        let len = self.test_status.len();
+        if self.test_logs.len() != len || self.test_durations.len() != len {
+            return Err(format!(
+                "{} statuses, {} logs and {} durations",
+                len,
+                self.test_logs.len(),
+                self.test_durations.len()
+            ));
+        }
+        if self.test_attempts.len() > len {
+            return Err(format!(
+                "{} attempts for {} tests",
+                self.test_attempts.len(),
+                len
+            ));
+        }
+        self.test_attempts.resize(len, Vec::new());
+
+        for (program, tests) in self.test_tree.iter() {
+            if let Some((test, index)) = tests.iter().find(|(_, index)| **index >= len) {
+                return Err(format!(
+                    "{} :: {} refers to test {} of {}",
+                    program, test, index, len
+                ));
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Folds another report into this one. Tests present in both keep the
+    /// failing status, collect the logs of both and sum their durations
+    pub fn merge(&mut self, other: CompiledRecord) {
+        let CompiledRecord {
+            test_tree,
+            test_status,
+            mut test_logs,
+            test_durations,
+            mut test_attempts,
+            program_runs,
+            program_attempts,
+            program_logs,
+            build_failures,
+            diagnostics,
+        } = other;
+
+        self.build_failures.extend(build_failures);
+        for (program, runs) in program_attempts {
+            self.program_attempts
+                .entry(program)
+                .or_default()
+                .extend(runs);
+        }
+        for (program, logs) in program_logs {
+            self.program_logs.entry(program).or_default().extend(logs);
+        }
+        for (program, diagnostics) in diagnostics {
+            self.diagnostics
+                .entry(program)
+                .or_default()
+                .extend(diagnostics);
+        }
+
+        for (program, tests) in test_tree {
+            for (test_name, other_index) in tests {
+                let status = test_status[other_index];
+                let logs = test_logs[other_index].take();
+                let duration = test_durations.get(other_index).copied().flatten();
+                let attempts = test_attempts
+                    .get_mut(other_index)
+                    .map(std::mem::take)
+                    .unwrap_or_default();
+
+                let program_tests = self.test_tree.entry(program.clone()).or_default();
+                let Some(&index) = program_tests.get(&test_name) else {
+                    program_tests.insert(test_name, self.test_status.len());
+                    self.test_status.push(status);
+                    self.test_logs.push(logs);
+                    self.test_durations.push(duration);
+                    self.test_attempts.push(attempts);
+                    continue;
+                };
+
+                self.test_status[index] = self.test_status[index].worst(status);
+                self.test_attempts[index].extend(attempts);
+
+                if let Some(logs) = logs {
+                    self.test_logs[index]
+                        .get_or_insert_with(Vec::new)
+                        .extend(logs);
+                }
+
+                self.test_durations[index] = match (self.test_durations[index], duration) {
+                    (Some(a), Some(b)) => Some(a + b),
+                    (a, b) => a.or(b),
+                };
+            }
+        }
+
+        for (program, run) in program_runs {
+            self.program_runs
+                .entry(program)
+                .and_modify(|existing| {
+                    existing.duration += run.duration;
+                    if !run.exit.success() {
+                        existing.exit = run.exit;
+                    }
+                })
+                .or_insert(run);
+        }
+    }
+
+    /// Failed tests per program. Programs whose failure can't be narrowed
+    /// down to tests they registered map to an empty list
+    pub fn failed_tests(&self) -> BTreeMap<String, Vec<String>> {
+        let mut failed = BTreeMap::new();
+
+        for (program, tests) in self.test_tree.iter() {
+            let mut names: Vec<(usize, &String)> = tests
+                .iter()
+                .filter(|(_, index)| !self.test_status[**index].passed())
+                .map(|(name, index)| (*index, name))
+                .collect();
+            names.sort();
+
+            let whole_program = names
+                .iter()
+                .any(|(_, name)| RUNNER_TEST_NAMES.contains(&name.as_str()))
+                || (names.is_empty()
+                    && self
+                        .program_runs
+                        .get(program)
+                        .is_some_and(|run| !run.exit.success()));
+
+            if whole_program {
+                failed.insert(program.clone(), Vec::new());
+            } else if !names.is_empty() {
+                failed.insert(
+                    program.clone(),
+                    names.into_iter().map(|(_, n)| n.clone()).collect(),
+                );
+            }
+        }
+
+        for program in self.build_failures.keys() {
+            failed.insert(program.clone(), Vec::new());
+        }
+
+        failed
+    }
+
+    /// Records the results of rerunning failed tests. Failed tests passing
+    /// on the retry become flaky, every attempt is kept for the report
+    pub fn apply_retry(&mut self, retry: CompiledRecord) {
+        let CompiledRecord {
+            test_tree,
+            test_status,
+            mut test_logs,
+            test_durations,
+            program_runs,
+            diagnostics,
+            ..
+        } = retry;
+
+        for (program, tests) in test_tree {
+            for (test_name, retry_index) in tests {
+                let status = test_status[retry_index];
+                let duration = test_durations[retry_index];
+                let logs = test_logs[retry_index].take();
+
+                let program_tests = self.test_tree.entry(program.clone()).or_default();
+                let Some(&index) = program_tests.get(&test_name) else {
+                    program_tests.insert(test_name, self.test_status.len());
+                    self.test_status.push(status);
+                    self.test_logs.push(logs);
+                    self.test_durations.push(duration);
+                    self.test_attempts.push(Vec::new());
+                    continue;
+                };
+
+                let attempts = &mut self.test_attempts[index];
+                if attempts.is_empty() {
+                    attempts.push(Attempt {
+                        status: self.test_status[index],
+                        duration: self.test_durations[index],
+                    });
+                }
+                attempts.push(Attempt { status, duration });
+
+                if !self.test_status[index].passed() && status.passed() {
+                    self.test_status[index] = TestStatus::Flaky;
+                }
+
+                if let Some(logs) = logs {
+                    self.test_logs[index]
+                        .get_or_insert_with(Vec::new)
+                        .extend(logs);
+                }
+            }
+        }
+
+        // earlier runs keep their exit, e.g. the crash that made the tests fail
+        for (program, run) in program_runs {
+            if let Some(previous) = self.program_runs.insert(program.clone(), run) {
+                self.program_attempts
+                    .entry(program)
+                    .or_default()
+                    .push(previous);
+            }
+        }
+        for (program, diagnostics) in diagnostics {
+            self.diagnostics
+                .entry(program)
+                .or_default()
+                .extend(diagnostics);
+        }
+    }
+
+    /// Replaces every program found in `other` by its new results,
+    /// used when only some executables were run again
+    pub fn replace_programs(&mut self, other: CompiledRecord) {
+        let previous = std::mem::replace(self, CompiledRecord::empty());
+        let CompiledRecord {
+            test_tree,
+            test_status,
+            mut test_logs,
+            test_durations,
+            mut test_attempts,
+            mut program_runs,
+            mut program_attempts,
+            mut program_logs,
+            mut build_failures,
+            mut diagnostics,
+        } = previous;
+
+        for (program, tests) in test_tree {
+            if other.test_tree.contains_key(&program) || other.program_runs.contains_key(&program) {
+                program_runs.remove(&program);
+                continue;
+            }
+
+            let mut kept = HashMap::new();
+            for (test_name, index) in tests {
+                kept.insert(test_name, self.test_status.len());
+                self.test_status.push(test_status[index]);
+                self.test_logs.push(test_logs[index].take());
+                self.test_durations.push(test_durations[index]);
+                self.test_attempts
+                    .push(std::mem::take(&mut test_attempts[index]));
+            }
+            self.test_tree.insert(program, kept);
+        }
+
+        program_runs.retain(|program, _| !other.program_runs.contains_key(program));
+        program_attempts.retain(|program, _| !other.program_runs.contains_key(program));
+        program_logs.retain(|program, _| !other.program_runs.contains_key(program));
+        build_failures.retain(|program, _| !other.program_runs.contains_key(program));
+        diagnostics.retain(|program, _| !other.program_runs.contains_key(program));
+        self.program_runs = program_runs;
+        self.program_attempts = program_attempts;
+        self.program_logs = program_logs;
+        self.build_failures = build_failures;
+        self.diagnostics = diagnostics;
+        self.merge(other);
+    }
+
+    /// Attaches diagnostics to a program. Each one is blamed on the topmost
+    /// test function of its stack, failing that test with the message.
+    /// Diagnostics without a test fail the program's `diagnostics` test
+    pub fn add_diagnostics(&mut self, program: &str, diagnostics: Vec<Diagnostic>) {
+        for mut diagnostic in diagnostics {
+            if diagnostic.test.is_none() {
+                let tests = self.test_tree.get(program);
+                diagnostic.test = diagnostic
+                    .frames
+                    .iter()
+                    .filter_map(|frame| frame.function.as_ref())
+                    .find(|function| tests.is_some_and(|t| t.contains_key(*function)))
+                    .cloned();
+            }
+
+            self.flag_test(
+                program,
+                diagnostic.test.as_deref().unwrap_or(DIAGNOSTICS_TEST_NAME),
+                TestStatus::Fail,
+                LogTypeMessage::Warning(match diagnostic.frames.first() {
+                    Some(frame) => format!(
+                        "{}: {} at {}",
+                        diagnostic.tool, diagnostic.kind, frame.location
+                    ),
+                    None => format!("{}: {}", diagnostic.tool, diagnostic.message),
+                }),
+            );
+
+            self.diagnostics
+                .entry(program.to_owned())
+                .or_default()
+                .push(diagnostic);
+        }
+    }
+
+    pub fn diagnostics(&self) -> &BTreeMap<String, Vec<Diagnostic>> {
+        &self.diagnostics
+    }
+
+    /// Records a program the build stage failed to compile
+    pub fn add_build_failure(&mut self, program: String, output: String) {
+        self.build_failures.insert(program, output);
+    }
+
+    pub fn build_failures(&self) -> &BTreeMap<String, String> {
+        &self.build_failures
+    }
+
+    /// Programs with their tests of the given status
+    pub fn tests_with_status(&self, status: TestStatus) -> Vec<(&str, Vec<&str>)> {
+        self.program_tests()
+            .into_iter()
+            .map(|(program, tests)| {
+                let matching = tests
+                    .into_iter()
+                    .filter(|test| self.test_status[self.test_tree[program][*test]] == status)
+                    .collect::<Vec<_>>();
+                (program, matching)
+            })
+            .filter(|(_, matching)| !matching.is_empty())
+            .collect()
+    }
+
+    /// Programs with their flaky tests
+    pub fn flaky_tests(&self) -> Vec<(&str, Vec<&str>)> {
+        self.tests_with_status(TestStatus::Flaky)
+    }
+
+    pub fn program_durations(&self) -> BTreeMap<String, Duration> {
+        self.program_runs
+            .iter()
+            .map(|(program, run)| (program.clone(), run.duration))
+            .collect()
+    }
+
+    /// Programs with their test names in registration order
+    pub fn program_tests(&self) -> Vec<(&str, Vec<&str>)> {
+        self.test_tree
+            .iter()
+            .map(|(program, tests)| {
+                let mut tests: Vec<(&String, &usize)> = tests.iter().collect();
+                tests.sort_by_key(|(_, index)| **index);
+
+                (
+                    program.as_str(),
+                    tests.into_iter().map(|(name, _)| name.as_str()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// Status and duration of a single test
+    pub fn test_result(&self, program: &str, test: &str) -> Option<(TestStatus, Option<Duration>)> {
+        let index = *self.test_tree.get(program)?.get(test)?;
+        Some((self.test_status[index], self.test_durations[index]))
+    }
+
+    /// Adds a log to a test, raising its status to `status` when more severe.
+    /// Missing tests are added, e.g. for checks done by the runner after a run
+    pub fn flag_test(
+        &mut self,
+        program: &str,
+        test: &str,
+        status: TestStatus,
+        log: LogTypeMessage,
+    ) {
+        let program_tests = self.test_tree.entry(program.to_owned()).or_default();
+        let Some(&index) = program_tests.get(test) else {
+            program_tests.insert(test.to_owned(), self.test_status.len());
+            self.test_status.push(status);
+            self.test_logs.push(Some(vec![log]));
+            self.test_durations.push(None);
+            self.test_attempts.push(Vec::new());
+            return;
+        };
+
+        self.test_status[index] = self.test_status[index].worst(status);
+        self.test_logs[index].get_or_insert_with(Vec::new).push(log);
+    }
+
+    /// Adds a log about the whole program, which leaves its tests untouched
+    pub fn log_program(&mut self, program: &str, log: LogTypeMessage) {
+        self.program_logs
+            .entry(program.to_owned())
+            .or_default()
+            .push(log);
+    }
+
+    // Executables not speaking the runtime protocol are recorded as a
+    // single test judged by their exit code. runtime.h executables exit
+    // with 0, another exit no failed test accounts for, e.g. valgrind's
+    // --error-exitcode, fails their exit code test as well
+    fn insert_exit_code_tests(&mut self, protocol_programs: &HashSet<String>) {
+        for (program, run) in self.program_runs.iter() {
+            let Some(tests) = self.test_tree.get_mut(program) else {
+                continue;
+            };
+
+            if protocol_programs.contains(program)
+                && (run.exit.success() || tests.values().any(|i| !self.test_status[*i].passed()))
+            {
+                continue;
+            }
+
+            let status = if run.exit.success() {
+                TestStatus::Success
+            } else {
+                TestStatus::Fail
+            };
+
+            let logs =
+                (!run.exit.success()).then(|| vec![LogTypeMessage::Warning(run.exit.to_string())]);
+
+            tests.insert(EXIT_CODE_TEST_NAME.to_owned(), self.test_status.len());
+            self.test_status.push(status);
+            self.test_logs.push(logs);
+            self.test_durations.push(Some(run.duration));
+            self.test_attempts.push(Vec::new());
+        }
+    }
+}
+
 impl Clone for TestRecord {
     fn clone(&self) -> Self {
         Self(self.0.clone())
@@ -256,13 +932,21 @@ impl StoreData for TestRecord {
                 // self.register_test(data.data.stat)
             },
             ProgramInfoType::Log => unsafe {
-                self.append_test_logs(data.data.log)
+                let log = data.data.log;
+                self.append_test_logs(&log.program_name, &log.function_name, log.into())
                 // self.register_test(data.data.log)
             },
+            ProgramInfoType::Hello => unsafe {
+                self.mark_protocol_program(&data.data.hello.program_name)
+            },
+            ProgramInfoType::Assert => unsafe {
+                let assert = data.data.assert;
+                self.append_test_logs(&assert.program_name, &assert.function_name, assert.into())
+            },
         };
 
         if let Err(it) = i {
-            println!(
+            progress!(
                 "{}FAIL: {:?}{}\n{}",
                 termion::color::Fg(color::Red),
                 it,
@@ -275,35 +959,255 @@ impl StoreData for TestRecord {
     }
 }
 
-
-
-impl Display for CompiledRecord{
+impl Display for CompiledRecord {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "CompiledRecord {{")?;
 
-        writeln!(f,"CompiledRecord {{")?;
+        for i in self.test_tree.iter() {
+            writeln!(f, "\t\"{}\"{{", i.0)?;
 
-        for i in self.test_tree.iter(){
-            writeln!(f,"\t\"{}\"{{", i.0)?;
+            for run in self.program_attempts.get(i.0).into_iter().flatten() {
+                writeln!(f, "\t\tEarlier run: {}", run.exit)?;
+            }
 
-            for (test_name, &index) in i.1.iter(){
+            for log in self.program_logs.get(i.0).into_iter().flatten() {
+                if let LogTypeMessage::Warning(msg) = log {
+                    writeln!(f, "\t\tWarning: {}", msg)?;
+                }
+            }
+
+            for (test_name, &index) in i.1.iter() {
                 let status = self.test_status[index];
-                let log_count = self.test_logs
+                let log_count = self
+                    .test_logs
                     .index(index)
                     .as_ref()
                     .map(Vec::len)
                     .unwrap_or(0);
 
-                writeln!(f,"\t\t\"{}\":\tStatus: {:?}\tLogs_count: {}", 
-                    test_name,
-                    status,
-                    log_count 
+                writeln!(
+                    f,
+                    "\t\t\"{}\":\tStatus: {:?}\tLogs_count: {}",
+                    test_name, status, log_count
                 )?;
+
+                // every failure message, a test with expectations can have several,
+                // and the stack of a crashed test
+                let logs = self.test_logs.index(index).iter().flatten();
+                for log in logs {
+                    match log {
+                        LogTypeMessage::Warning(msg) => writeln!(f, "\t\t\t{}", msg)?,
+                        LogTypeMessage::Assertion(failure) => writeln!(f, "\t\t\t{}", failure)?,
+                        // frames follow the crash warning, nested under it
+                        LogTypeMessage::Backtrace(frame) => writeln!(f, "\t\t\t\t{}", frame)?,
+                        _ => {}
+                    }
+                }
             }
-            writeln!(f,"\t}},")?;
+            writeln!(f, "\t}},")?;
+        }
+
+        // tests that only passed on a retry, with the status of every attempt
+        let flaky = self.flaky_tests();
+        if !flaky.is_empty() {
+            writeln!(f, "\tFlaky {{")?;
+
+            for (program, tests) in flaky {
+                for test in tests {
+                    let index = self.test_tree[program][test];
+                    let attempts = self.test_attempts[index]
+                        .iter()
+                        .map(|a| format!("{:?}", a.status))
+                        .collect::<Vec<_>>()
+                        .join(" -> ");
+
+                    writeln!(
+                        f,
+                        "\t\t\"{}\" :: \"{}\"\tAttempts: {}",
+                        program, test, attempts
+                    )?;
+                }
+            }
+            writeln!(f, "\t}},")?;
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::record_collection::AssertionFailure;
+
+    fn single_test_record(
+        program: &str,
+        test: &str,
+        status: TestStatus,
+        secs: u64,
+    ) -> CompiledRecord {
+        CompiledRecord {
+            test_tree: BTreeMap::from([(
+                program.to_string(),
+                HashMap::from([(test.to_string(), 0)]),
+            )]),
+            test_status: vec![status],
+            test_logs: vec![Some(vec![LogTypeMessage::Info(format!("{}s", secs))])],
+            test_durations: vec![Some(Duration::from_secs(secs))],
+            test_attempts: vec![Vec::new()],
+            program_runs: BTreeMap::from([(
+                program.to_string(),
+                ProgramRun {
+                    exit: ProcessExit::Exited(0),
+                    duration: Duration::from_secs(secs),
+                },
+            )]),
+            program_attempts: BTreeMap::new(),
+            program_logs: BTreeMap::new(),
+            build_failures: BTreeMap::new(),
+            diagnostics: BTreeMap::new(),
         }
+    }
 
+    #[test]
+    fn merge_reconciles_duplicate_tests() {
+        let mut merged = CompiledRecord::empty();
+        merged.merge(single_test_record("bin/a", "add", TestStatus::Fail, 1));
+        merged.merge(single_test_record("bin/a", "add", TestStatus::Success, 2));
+        merged.merge(single_test_record("bin/b", "mul", TestStatus::Success, 4));
+
+        assert_eq!(
+            merged.program_tests(),
+            vec![("bin/a", vec!["add"]), ("bin/b", vec!["mul"])]
+        );
+
+        let add = merged.test_tree["bin/a"]["add"];
+        assert!(matches!(merged.test_status[add], TestStatus::Fail));
+        assert_eq!(merged.test_logs[add].as_ref().map(Vec::len), Some(2));
+        assert_eq!(merged.test_durations[add], Some(Duration::from_secs(3)));
+        assert_eq!(
+            merged.program_runs["bin/a"].duration,
+            Duration::from_secs(3)
+        );
+    }
 
+    #[test]
+    fn validate_rejects_dangling_indices() {
+        let mut record = single_test_record("bin/a", "add", TestStatus::Success, 1);
+        record.test_attempts.clear();
+        let record = record.validate().unwrap();
+        assert_eq!(record.test_attempts.len(), 1);
+
+        let mut dangling = single_test_record("bin/a", "add", TestStatus::Success, 1);
+        dangling
+            .test_tree
+            .get_mut("bin/a")
+            .unwrap()
+            .insert("mul".to_owned(), 7);
+        assert!(dangling.validate().is_err());
+
+        let mut short = single_test_record("bin/a", "add", TestStatus::Success, 1);
+        short.test_logs.clear();
+        assert!(short.validate().is_err());
+    }
 
-        writeln!(f,"}}")
+    #[test]
+    fn unattributed_diagnostic_fails_the_program() {
+        let mut record = single_test_record("bin/a", "add", TestStatus::Success, 1);
+        record.add_diagnostics(
+            "bin/a",
+            vec![Diagnostic {
+                tool: "AddressSanitizer".to_owned(),
+                kind: "heap-use-after-free".to_owned(),
+                message: "heap-use-after-free on address".to_owned(),
+                test: None,
+                frames: Vec::new(),
+            }],
+        );
+
+        let (status, _) = record.test_result("bin/a", DIAGNOSTICS_TEST_NAME).unwrap();
+        assert_eq!(status, TestStatus::Fail);
+        assert_eq!(
+            record.test_result("bin/a", "add").unwrap().0,
+            TestStatus::Success
+        );
+        assert_eq!(
+            record.failed_tests(),
+            BTreeMap::from([("bin/a".to_owned(), Vec::new())])
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn unexplained_exit_fails_protocol_program() {
+        let mut record = single_test_record("bin/a", "add", TestStatus::Success, 1);
+        record.program_runs.get_mut("bin/a").unwrap().exit = ProcessExit::Exited(99);
+        record.insert_exit_code_tests(&HashSet::from(["bin/a".to_owned()]));
+
+        let (status, _) = record.test_result("bin/a", EXIT_CODE_TEST_NAME).unwrap();
+        assert_eq!(status, TestStatus::Fail);
+    }
+
+    #[test]
+    fn retry_passing_marks_flaky() {
+        let mut record = single_test_record("bin/a", "add", TestStatus::Fail, 1);
+        record.program_runs.get_mut("bin/a").unwrap().exit = ProcessExit::Signaled(11);
+        record.apply_retry(single_test_record("bin/a", "add", TestStatus::Success, 2));
+
+        let add = record.test_tree["bin/a"]["add"];
+        assert!(matches!(record.test_status[add], TestStatus::Flaky));
+        assert_eq!(record.test_attempts[add].len(), 2);
+        assert!(record.failed_tests().is_empty());
+        assert_eq!(record.flaky_tests(), vec![("bin/a", vec!["add"])]);
+        assert!(matches!(
+            record.program_attempts["bin/a"][0].exit,
+            ProcessExit::Signaled(11)
+        ));
+        assert!(
+            record
+                .to_string()
+                .contains("Earlier run: Terminated by signal 11")
+        );
+        assert!(
+            record
+                .to_string()
+                .contains("\"bin/a\" :: \"add\"\tAttempts: Fail -> Success")
+        );
+    }
+
+    #[test]
+    fn replace_programs_drops_old_results() {
+        let mut record = single_test_record("bin/a", "add", TestStatus::Fail, 1);
+        record.merge(single_test_record("bin/b", "mul", TestStatus::Fail, 1));
+        record.replace_programs(single_test_record("bin/a", "sub", TestStatus::Success, 2));
+
+        assert_eq!(
+            record.program_tests(),
+            vec![("bin/a", vec!["sub"]), ("bin/b", vec!["mul"])]
+        );
+        assert_eq!(record.program_durations()["bin/a"], Duration::from_secs(2));
+        assert_eq!(
+            record.failed_tests().keys().collect::<Vec<_>>(),
+            vec!["bin/b"]
+        );
+    }
+
+    #[test]
+    fn text_report_lists_failure_messages() {
+        let mut record = single_test_record("bin/a", "add", TestStatus::Fail, 1);
+        record.test_logs[0] = Some(vec![
+            LogTypeMessage::Assertion(AssertionFailure {
+                expected: "5".to_string(),
+                actual: "4".to_string(),
+                location: "add.c:7".to_string(),
+            }),
+            LogTypeMessage::Warning("Expectation failed: add.c:7".to_string()),
+            LogTypeMessage::Debug("hidden".to_string()),
+        ]);
+
+        let text = record.to_string();
+        assert!(
+            text.contains("\t\t\texpected 5, got 4 (add.c:7)\n\t\t\tExpectation failed: add.c:7\n")
+        );
+        assert!(!text.contains("hidden"));
+    }
+}
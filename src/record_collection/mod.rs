@@ -1,4 +1,6 @@
-use std::{fmt::Display, io};
+use std::{fmt::Display, time::Duration};
+
+use serde::{Deserialize, Serialize};
 
 use crate::util;
 
@@ -21,9 +23,69 @@ const PROGRAM_NAME_MAX_CHAR_SIZE: usize = 64;
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
+#[expect(dead_code, reason = "only read from the bytes sent by runtime.h")]
 enum StatusType {
     Success,
     Fail,
+    // the test thread took the whole process down
+    Crash,
+    // a setup or teardown fixture of the test failed
+    SetupFail,
+    // still running when another test crashed the process
+    Interrupted,
+}
+
+/// Status of a test as kept by the runner, the statuses sent by
+/// runtime.h plus the ones only the runner can tell
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TestStatus {
+    Success,
+    Fail,
+    Crash,
+    SetupFail,
+    Interrupted,
+    // failed at first but passed on a retry
+    Flaky,
+}
+
+impl TestStatus {
+    #[inline]
+    pub fn passed(&self) -> bool {
+        matches!(self, TestStatus::Success | TestStatus::Flaky)
+    }
+
+    // Ordering used when the same test has several results
+    fn severity(&self) -> u8 {
+        match self {
+            TestStatus::Success => 0,
+            TestStatus::Flaky => 1,
+            TestStatus::Interrupted => 2,
+            TestStatus::Fail => 3,
+            TestStatus::SetupFail => 4,
+            TestStatus::Crash => 5,
+        }
+    }
+
+    /// The more severe of both statuses
+    pub fn worst(self, other: TestStatus) -> TestStatus {
+        if other.severity() > self.severity() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+impl From<StatusType> for TestStatus {
+    fn from(value: StatusType) -> Self {
+        match value {
+            StatusType::Success => TestStatus::Success,
+            StatusType::Fail => TestStatus::Fail,
+            StatusType::Crash => TestStatus::Crash,
+            StatusType::SetupFail => TestStatus::SetupFail,
+            StatusType::Interrupted => TestStatus::Interrupted,
+        }
+    }
 }
 
 #[repr(C)]
@@ -32,6 +94,7 @@ struct Status {
     program_name: [u8; PROGRAM_NAME_MAX_CHAR_SIZE],
     function_name: [u8; FUNCTION_MAX_CHAR_SIZE],
     t: StatusType,
+    duration_ns: u64,
 }
 
 impl Display for Status {
@@ -69,10 +132,12 @@ impl Display for Register {
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
+#[expect(dead_code, reason = "only read from the bytes sent by runtime.h")]
 enum LogType {
     Debug,
     Info,
     Warning,
+    Backtrace,
 }
 
 #[repr(C)]
@@ -98,18 +163,65 @@ impl Display for Log {
     }
 }
 
+// Values of a failed typed assertion, e.g. `assert_eq_int`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Assert {
+    program_name: [u8; PROGRAM_NAME_MAX_CHAR_SIZE],
+    function_name: [u8; FUNCTION_MAX_CHAR_SIZE],
+    expected: [u8; MESSAGE_BUFFER],
+    actual: [u8; MESSAGE_BUFFER],
+    location: [u8; MESSAGE_BUFFER],
+}
+
+impl Display for Assert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let program_name = String::from_utf8_lossy(&self.program_name);
+        let func_name = String::from_utf8_lossy(&self.function_name);
+        let expected = String::from_utf8_lossy(&self.expected);
+        let actual = String::from_utf8_lossy(&self.actual);
+
+        write!(
+            f,
+            "{{ \n\tprogram_name: {},\n\tfunction_name: {},\n\texpected: {},\n\tactual: {} \n}}",
+            program_name, func_name, expected, actual,
+        )
+    }
+}
+
+// First frame of every runtime.h program, sent
+// before any test is registered
 #[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Hello {
+    program_name: [u8; PROGRAM_NAME_MAX_CHAR_SIZE],
+}
+
+impl Display for Hello {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let program_name = String::from_utf8_lossy(&self.program_name);
+
+        write!(f, "{{ \n\tprogram_name: {}\n}}", program_name)
+    }
+}
+
+#[repr(C)]
+#[expect(dead_code, reason = "only read from the bytes sent by runtime.h")]
 enum ProgramInfoType {
     Register = 0,
     Status = 1,
     Log = 2,
+    Hello = 3,
+    Assert = 4,
 }
 
 #[repr(C)]
 pub union ProgramData {
-    pub log: Log,
-    pub reg: Register,
-    pub stat: Status,
+    log: Log,
+    reg: Register,
+    stat: Status,
+    hello: Hello,
+    assert: Assert,
 }
 
 #[repr(C)]
@@ -124,15 +236,54 @@ impl Display for ProcessInfo {
             ProgramInfoType::Register => unsafe { write!(f, "[Register]{}", self.data.reg) },
             ProgramInfoType::Status => unsafe { write!(f, "[Status]{}", self.data.stat) },
             ProgramInfoType::Log => unsafe { write!(f, "[Log]{}", self.data.log) },
+            ProgramInfoType::Hello => unsafe { write!(f, "[Hello]{}", self.data.hello) },
+            ProgramInfoType::Assert => unsafe { write!(f, "[Assert]{}", self.data.assert) },
         }
     }
 }
 
-#[derive(Debug)]
-enum LogTypeMessage {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LogTypeMessage {
     Debug(String),
     Info(String),
     Warning(String),
+    // frame of a crashed test's stack, one log per frame
+    Backtrace(String),
+    // unified diff produced by the runner, never sent by the C runtime
+    Diff(String),
+    Assertion(AssertionFailure),
+}
+
+/// Expected and actual values of a failed typed assertion
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssertionFailure {
+    pub expected: String,
+    pub actual: String,
+    pub location: String,
+}
+
+impl Display for AssertionFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "expected {}, got {} ({})",
+            self.expected, self.actual, self.location
+        )
+    }
+}
+
+impl From<Assert> for LogTypeMessage {
+    fn from(value: Assert) -> Self {
+        let field = |bytes: &[u8]| {
+            util::bytes_to_trimmed_string(bytes).unwrap_or("[Data Courrpted]".to_string())
+        };
+
+        LogTypeMessage::Assertion(AssertionFailure {
+            expected: field(&value.expected),
+            actual: field(&value.actual),
+            location: field(&value.location),
+        })
+    }
 }
 
 impl From<Log> for LogTypeMessage {
@@ -147,10 +298,75 @@ impl From<Log> for LogTypeMessage {
             LogType::Warning => LogTypeMessage::Warning(
                 util::bytes_to_trimmed_string(&value.msg).unwrap_or("[Data Courrpted]".to_string()),
             ),
+            LogType::Backtrace => LogTypeMessage::Backtrace(
+                util::bytes_to_trimmed_string(&value.msg).unwrap_or("[Data Courrpted]".to_string()),
+            ),
         }
     }
 }
 
+/// How a spawned executable terminated
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum ProcessExit {
+    Exited(i32),
+    Signaled(i32),
+    Stopped(i32),
+    Unknown,
+    // the scratch directory or the process could not be set up
+    NotStarted,
+}
+
+impl ProcessExit {
+    #[inline]
+    pub fn success(&self) -> bool {
+        matches!(self, ProcessExit::Exited(0))
+    }
+}
+
+impl Display for ProcessExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessExit::Exited(code) => write!(f, "Exited with code {}", code),
+            ProcessExit::Signaled(signal) => write!(f, "Terminated by signal {}", signal),
+            ProcessExit::Stopped(signal) => write!(f, "Stopped by signal {}", signal),
+            ProcessExit::Unknown => write!(f, "Undefined termination"),
+            ProcessExit::NotStarted => write!(f, "Failed to start"),
+        }
+    }
+}
+
+/// Result of a single run of a test that was retried
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Attempt {
+    pub status: TestStatus,
+    pub duration: Option<Duration>,
+}
+
+/// Termination and wall time of a spawned executable
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ProgramRun {
+    pub exit: ProcessExit,
+    pub duration: Duration,
+}
+
+/// Frame of a stack trace reported by a tool inspecting the run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StackFrame {
+    pub function: Option<String>,
+    pub location: String,
+}
+
+/// Error reported by a tool inspecting the run, e.g. a sanitizer,
+/// attributed to the test whose function shows up in the stack
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub tool: String,
+    pub kind: String,
+    pub message: String,
+    pub test: Option<String>,
+    pub frames: Vec<StackFrame>,
+}
+
 #[inline(always)]
 pub fn bin_convert(refe: &[u8; std::mem::size_of::<ProcessInfo>()]) -> ProcessInfo {
     unsafe { std::ptr::read(refe.as_ptr() as *const ProcessInfo) }
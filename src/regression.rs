@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use termion::color;
+
+use crate::{
+    configs::ConfigRegression,
+    record_collection::{LogTypeMessage, TestStatus, collection::CompiledRecord},
+};
+
+/// Test of an executable whose total duration regressed
+pub const DURATION_TEST_NAME: &str = "duration_regression";
+
+/// A test, or the whole executable without `test`, slower than in the baseline
+#[derive(Debug, PartialEq)]
+pub struct Regression {
+    pub program: String,
+    pub test: Option<String>,
+    pub baseline: Duration,
+    pub current: Duration,
+}
+
+impl Regression {
+    fn message(&self) -> String {
+        format!(
+            "Duration regressed from {:?} to {:?}",
+            self.baseline, self.current
+        )
+    }
+}
+
+fn regressed(config: &ConfigRegression, baseline: Duration, current: Duration) -> bool {
+    let baseline_secs = baseline.as_secs_f64();
+    current.as_secs_f64() > baseline_secs * (1.0 + config.relative)
+        && current.saturating_sub(baseline) > Duration::from_millis(config.absolute_ms)
+}
+
+/// Tests and executables of `current` exceeding both thresholds,
+/// entries missing from the baseline are never reported
+pub fn find_regressions(
+    baseline: &CompiledRecord,
+    current: &CompiledRecord,
+    config: &ConfigRegression,
+) -> Vec<Regression> {
+    let mut regressions = Vec::new();
+    let baseline_runs = baseline.program_durations();
+
+    for (program, duration) in current.program_durations() {
+        if let Some(&before) = baseline_runs.get(&program)
+            && regressed(config, before, duration)
+        {
+            regressions.push(Regression {
+                program: program.clone(),
+                test: None,
+                baseline: before,
+                current: duration,
+            });
+        }
+    }
+
+    for (program, tests) in current.program_tests() {
+        for test in tests {
+            let Some((_, Some(duration))) = current.test_result(program, test) else {
+                continue;
+            };
+            let Some((_, Some(before))) = baseline.test_result(program, test) else {
+                continue;
+            };
+
+            if regressed(config, before, duration) {
+                regressions.push(Regression {
+                    program: program.to_owned(),
+                    test: Some(test.to_owned()),
+                    baseline: before,
+                    current: duration,
+                });
+            }
+        }
+    }
+
+    regressions
+}
+
+/// Records the regressions as warnings on their tests and executables, or
+/// as failures with `fail`. Regressed executables then fail a
+/// `duration_regression` test
+pub fn apply_regressions(
+    record: &mut CompiledRecord,
+    regressions: &[Regression],
+    config: &ConfigRegression,
+) {
+    let tint = if config.fail {
+        color::Fg(color::Red).to_string()
+    } else {
+        color::Fg(color::Yellow).to_string()
+    };
+
+    for regression in regressions {
+        let message = regression.message();
+
+        println!(
+            "{}[ {}: {} :: {} ]{}",
+            tint,
+            message,
+            regression.program,
+            regression.test.as_deref().unwrap_or("*"),
+            color::Fg(color::Reset)
+        );
+
+        let log = LogTypeMessage::Warning(message);
+        match (regression.test.as_deref(), config.fail) {
+            (Some(test), fail) => {
+                let status = if fail {
+                    TestStatus::Fail
+                } else {
+                    TestStatus::Success
+                };
+                record.flag_test(&regression.program, test, status, log);
+            }
+            (None, true) => record.flag_test(
+                &regression.program,
+                DURATION_TEST_NAME,
+                TestStatus::Fail,
+                log,
+            ),
+            (None, false) => record.log_program(&regression.program, log),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn both_thresholds_must_be_exceeded() {
+        let config = ConfigRegression {
+            relative: 0.5,
+            absolute_ms: 10,
+            fail: false,
+        };
+        let ms = Duration::from_millis;
+
+        assert!(regressed(&config, ms(100), ms(200)));
+        // relative only
+        assert!(!regressed(&config, ms(2), ms(8)));
+        // absolute only
+        assert!(!regressed(&config, ms(100), ms(140)));
+    }
+
+    #[test]
+    fn program_regression_fails_only_with_fail() {
+        let regression = Regression {
+            program: "bin/a".to_owned(),
+            test: None,
+            baseline: Duration::from_millis(100),
+            current: Duration::from_millis(300),
+        };
+        let mut config = ConfigRegression {
+            relative: 0.5,
+            absolute_ms: 10,
+            fail: false,
+        };
+
+        let mut warned = CompiledRecord::empty();
+        warned.flag_test(
+            "bin/a",
+            "add",
+            TestStatus::Success,
+            LogTypeMessage::Info(String::new()),
+        );
+        apply_regressions(&mut warned, std::slice::from_ref(&regression), &config);
+        assert!(warned.test_result("bin/a", DURATION_TEST_NAME).is_none());
+        assert!(warned.to_string().contains("Warning: Duration regressed"));
+
+        config.fail = true;
+        let mut failed = CompiledRecord::empty();
+        apply_regressions(&mut failed, &[regression], &config);
+        assert!(matches!(
+            failed.test_result("bin/a", DURATION_TEST_NAME),
+            Some((TestStatus::Fail, None))
+        ));
+    }
+}
@@ -0,0 +1,51 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    args::OutputFormat, get_global_config_ref, record_collection::collection::CompiledRecord,
+};
+
+pub const REPORT_FILE: &str = "report.json";
+
+/// Location of the report written after every run
+pub fn default_report_path() -> PathBuf {
+    Path::new(&get_global_config_ref().path.report_out).join(REPORT_FILE)
+}
+
+pub fn write_report(record: &CompiledRecord, path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(path, format_report(record, OutputFormat::Json)?)
+}
+
+pub fn read_report(path: &Path) -> io::Result<CompiledRecord> {
+    let json = fs::read_to_string(path)?;
+    serde_json::from_str::<CompiledRecord>(&json)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        .validate()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn format_report(record: &CompiledRecord, format: OutputFormat) -> io::Result<String> {
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(record).map_err(io::Error::other),
+        OutputFormat::Text => Ok(record.to_string()),
+    }
+}
+
+/// Combines the reports at `paths` in the given order
+pub fn merge_reports<P: AsRef<Path>>(paths: &[P]) -> io::Result<CompiledRecord> {
+    let mut merged = CompiledRecord::empty();
+
+    for path in paths {
+        let record = read_report(path.as_ref())
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.as_ref().display(), e)))?;
+        merged.merge(record);
+    }
+
+    Ok(merged)
+}
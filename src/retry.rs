@@ -0,0 +1,46 @@
+use termion::color;
+
+use crate::{
+    collect::FileCollection, get_global_config_ref, record_collection::collection::CompiledRecord,
+    spawner::spawn_executable,
+};
+
+/// Runs the collection, then reruns the failed tests up to the configured
+/// retry count. Tests failing first and passing later are reported flaky
+pub fn run_with_retries(file_collection: FileCollection) -> Option<CompiledRecord> {
+    let retries = get_global_config_ref().retry.count;
+    let mut record = spawn_executable(file_collection.clone())?;
+
+    for attempt in 1..=retries {
+        let failed = record.failed_tests();
+        if failed.is_empty() {
+            break;
+        }
+
+        let mut subset = file_collection.clone();
+        subset.retain(|path| failed.contains_key(path));
+        if subset.len() == 0 {
+            break;
+        }
+        subset.test_names = failed
+            .iter()
+            .filter(|(_, tests)| !tests.is_empty())
+            .map(|(program, tests)| (program.clone(), tests.clone()))
+            .collect();
+
+        println!(
+            "{}[ Retry {}/{}: {} executables ]{}",
+            color::Fg(color::Yellow),
+            attempt,
+            retries,
+            subset.len(),
+            color::Fg(color::Reset)
+        );
+
+        if let Some(retry) = spawn_executable(subset) {
+            record.apply_retry(retry);
+        }
+    }
+
+    Some(record)
+}
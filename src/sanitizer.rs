@@ -0,0 +1,172 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::{
+    configs::ConfigSanitizer,
+    record_collection::{Diagnostic, StackFrame},
+};
+
+// Options variable and runner defaults of each sanitizer
+const SANITIZER_OPTIONS: [(&str, &str, &str); 4] = [
+    ("address", "ASAN_OPTIONS", "halt_on_error=1:detect_leaks=1"),
+    ("undefined", "UBSAN_OPTIONS", "print_stacktrace=1"),
+    ("thread", "TSAN_OPTIONS", "second_deadlock_stack=1"),
+    ("leak", "LSAN_OPTIONS", ""),
+];
+
+static REPORT_HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:ERROR|WARNING): (\w+Sanitizer): (.*)$").expect("valid sanitizer header")
+});
+
+static UB_HEADER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(.*?): runtime error: (.*)$").expect("valid runtime error"));
+
+static FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*#\d+ 0x[0-9a-f]+ (?:in (\S+) )?(.*)$").expect("valid stack frame")
+});
+
+/// Compiler flags instrumenting test sources with the enabled sanitizers
+pub fn build_flags(config: &ConfigSanitizer) -> Vec<String> {
+    if !config.is_enabled() {
+        return Vec::new();
+    }
+
+    vec![
+        format!("-fsanitize={}", config.enabled.join(",")),
+        "-fno-omit-frame-pointer".to_owned(),
+        "-g".to_owned(),
+    ]
+}
+
+/// Options of the enabled sanitizers handed to every child
+pub fn sanitizer_env(config: &ConfigSanitizer) -> Vec<(String, String)> {
+    SANITIZER_OPTIONS
+        .iter()
+        .filter(|(name, ..)| config.enabled.iter().any(|enabled| enabled == name))
+        .map(|(_, var, default)| {
+            let value = config.options.get(*var).map_or(*default, String::as_str);
+            (var.to_string(), value.to_owned())
+        })
+        .collect()
+}
+
+// Error kind of a header, e.g. `heap-buffer-overflow` out of
+// `heap-buffer-overflow on address 0x...` or `data race (pid=1)`
+fn report_kind(message: &str) -> String {
+    message
+        .split([' ', ':'])
+        .take_while(|word| !matches!(*word, "on" | "in" | "") && !word.starts_with('('))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Reports found in the stderr of a sanitized program, each with the
+/// first `max_frames` frames of its first stack
+pub fn parse_reports(stderr: &str, max_frames: usize) -> Vec<Diagnostic> {
+    let mut reports: Vec<Diagnostic> = Vec::new();
+    // the first stack of a report ends at the first line that isn't a frame
+    let mut in_stack = false;
+    let mut stack_done = true;
+
+    for line in stderr.lines() {
+        if let Some(header) = REPORT_HEADER.captures(line) {
+            reports.push(Diagnostic {
+                tool: header[1].to_owned(),
+                kind: report_kind(&header[2]),
+                message: header[2].trim().to_owned(),
+                test: None,
+                frames: Vec::new(),
+            });
+            (in_stack, stack_done) = (false, false);
+            continue;
+        }
+
+        if let Some(header) = UB_HEADER.captures(line) {
+            let message = header[2].trim();
+            reports.push(Diagnostic {
+                tool: "UndefinedBehaviorSanitizer".to_owned(),
+                kind: message.split(':').next().unwrap_or(message).to_owned(),
+                message: message.to_owned(),
+                test: None,
+                frames: vec![StackFrame {
+                    function: None,
+                    location: header[1].to_owned(),
+                }],
+            });
+            (in_stack, stack_done) = (false, false);
+            continue;
+        }
+
+        let Some(report) = reports.last_mut() else {
+            continue;
+        };
+        if stack_done {
+            continue;
+        }
+
+        match FRAME.captures(line) {
+            Some(frame) => {
+                // the header location of a runtime error is replaced by the stack
+                if !in_stack && report.frames.first().is_some_and(|f| f.function.is_none()) {
+                    report.frames.clear();
+                }
+                in_stack = true;
+
+                if report.frames.len() < max_frames {
+                    report.frames.push(StackFrame {
+                        function: frame.get(1).map(|f| f.as_str().to_owned()),
+                        location: frame[2].trim().to_owned(),
+                    });
+                }
+            }
+            None if in_stack => stack_done = true,
+            None => {}
+        }
+    }
+
+    reports
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_address_and_undefined_reports() {
+        let stderr = "\
+=================================================================
+==1==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602 at pc 0x5 bp 0x7 sp 0x7
+WRITE of size 4 at 0x602000000020 thread T2
+    #0 0x564f47665e5d in overflow /tmp/asan.c:10
+    #1 0x7fb5398a71f4  (/lib/x86_64-linux-gnu/libc.so.6+0x891f4)
+
+allocated by thread T2 here:
+    #0 0x7fb53a2b89cf in __interceptor_malloc asan_malloc_linux.cpp:69
+/tmp/ub.c:5:7: runtime error: signed integer overflow: 2147483647 + 1 cannot be represented in type 'int'
+    #0 0x55 in add /tmp/ub.c:5
+";
+
+        let reports = parse_reports(stderr, 5);
+        assert_eq!(reports.len(), 2);
+
+        assert_eq!(reports[0].tool, "AddressSanitizer");
+        assert_eq!(reports[0].kind, "heap-buffer-overflow");
+        assert_eq!(reports[0].frames.len(), 2);
+        assert_eq!(reports[0].frames[0].function.as_deref(), Some("overflow"));
+        assert_eq!(reports[0].frames[0].location, "/tmp/asan.c:10");
+
+        assert_eq!(reports[1].kind, "signed integer overflow");
+        assert_eq!(reports[1].frames.len(), 1);
+        assert_eq!(reports[1].frames[0].function.as_deref(), Some("add"));
+    }
+
+    #[test]
+    fn thread_sanitizer_kind() {
+        assert_eq!(report_kind("data race (pid=42)"), "data race");
+        assert_eq!(
+            report_kind("detected memory leaks"),
+            "detected memory leaks"
+        );
+    }
+}
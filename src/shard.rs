@@ -0,0 +1,138 @@
+use std::{collections::BTreeMap, time::Duration};
+
+/// One of `count` disjoint parts of the collected executables,
+/// written as `index/count` on the command line with a 1-based index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Shard {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (index, count) = value.split_once('/')?;
+        let index: usize = index.trim().parse().ok()?;
+        let count: usize = count.trim().parse().ok()?;
+
+        (index >= 1 && index <= count).then_some(Self { index, count })
+    }
+
+    /// Executables of `paths` belonging to this shard, every machine
+    /// computes the same assignment for the same executables and timings
+    pub fn assign<'a>(
+        &self,
+        paths: &[&'a str],
+        timings: Option<&BTreeMap<String, Duration>>,
+    ) -> Vec<&'a str> {
+        match timings {
+            Some(timings) => self.assign_balanced(paths, timings),
+            None => paths
+                .iter()
+                .copied()
+                .filter(|p| (fnv1a(p.as_bytes()) % self.count as u64) as usize == self.index - 1)
+                .collect(),
+        }
+    }
+
+    // Longest executables first, each going to the least loaded shard.
+    // Executables missing from the timings count as the average duration
+    fn assign_balanced<'a>(
+        &self,
+        paths: &[&'a str],
+        timings: &BTreeMap<String, Duration>,
+    ) -> Vec<&'a str> {
+        let known: Vec<Duration> = paths
+            .iter()
+            .filter_map(|p| timings.get(*p))
+            .copied()
+            .collect();
+        let average = if known.is_empty() {
+            Duration::ZERO
+        } else {
+            known.iter().sum::<Duration>() / known.len() as u32
+        };
+
+        let mut weighted: Vec<(&str, Duration)> = paths
+            .iter()
+            .map(|p| (*p, timings.get(*p).copied().unwrap_or(average)))
+            .collect();
+        weighted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+        let mut load = vec![Duration::ZERO; self.count];
+        let mut selected = Vec::new();
+
+        for (path, duration) in weighted {
+            let (lightest, _) = load
+                .iter()
+                .enumerate()
+                .min_by_key(|(i, l)| (**l, *i))
+                .expect("Shard count is never zero");
+
+            load[lightest] += duration;
+            if lightest == self.index - 1 {
+                selected.push(path);
+            }
+        }
+
+        selected
+    }
+}
+
+// Stable across platforms and compiler versions, unlike the std hasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PATHS: [&str; 6] = ["bin/a", "bin/b", "bin/c", "bin/d", "bin/e", "bin/f"];
+
+    fn all_shards(count: usize, timings: Option<&BTreeMap<String, Duration>>) -> Vec<Vec<&str>> {
+        (1..=count)
+            .map(|index| Shard { index, count }.assign(&PATHS, timings))
+            .collect()
+    }
+
+    #[test]
+    fn parse_rejects_out_of_range() {
+        assert_eq!(Shard::parse("2/3"), Some(Shard { index: 2, count: 3 }));
+        assert_eq!(Shard::parse("0/3"), None);
+        assert_eq!(Shard::parse("4/3"), None);
+        assert_eq!(Shard::parse("1"), None);
+    }
+
+    #[test]
+    fn shards_partition_every_executable_once() {
+        let timings = BTreeMap::from([
+            ("bin/a".to_string(), Duration::from_secs(8)),
+            ("bin/b".to_string(), Duration::from_secs(4)),
+            ("bin/c".to_string(), Duration::from_secs(4)),
+        ]);
+
+        for timings in [None, Some(&timings)] {
+            let mut merged: Vec<&str> = all_shards(3, timings).concat();
+            merged.sort();
+            assert_eq!(merged, PATHS);
+        }
+    }
+
+    #[test]
+    fn balanced_by_timings() {
+        let timings = BTreeMap::from([
+            ("bin/a".to_string(), Duration::from_secs(9)),
+            ("bin/b".to_string(), Duration::from_secs(5)),
+            ("bin/c".to_string(), Duration::from_secs(4)),
+            ("bin/d".to_string(), Duration::from_secs(1)),
+            ("bin/e".to_string(), Duration::from_secs(1)),
+            ("bin/f".to_string(), Duration::from_secs(1)),
+        ]);
+
+        let shards = all_shards(2, Some(&timings));
+        assert_eq!(shards[0], vec!["bin/a", "bin/d", "bin/f"]);
+        assert_eq!(shards[1], vec!["bin/b", "bin/c", "bin/e"]);
+    }
+}
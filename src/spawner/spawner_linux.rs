@@ -1,13 +1,21 @@
+use std::collections::VecDeque;
 use std::ffi::CString;
+use std::os::fd::AsRawFd;
 use std::os::fd::OwnedFd;
+use std::os::fd::RawFd;
+use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::thread;
 use std::thread::sleep;
 use std::time::Duration;
+use std::time::Instant;
 
+use crate::configs::{PathPattern, SpawnOptions};
+use crate::get_global_args_ref;
 use crate::get_global_config_ref;
-use crate::record_collection::collection::CompiledRecord;
+use crate::listing;
+use crate::record_collection::collection::{CompiledRecord, EXIT_CODE_TEST_NAME};
 use crate::record_collection::collection::TestRecord;
 
 use nix::libc::WEXITSTATUS;
@@ -17,7 +25,9 @@ use nix::libc::WIFSTOPPED;
 use nix::libc::WNOHANG;
 use nix::libc::WSTOPSIG;
 use nix::libc::WTERMSIG;
+use nix::libc::WCOREDUMP;
 use nix::libc::posix_spawn;
+use nix::libc::posix_spawnp;
 use nix::libc::posix_spawn_file_actions_destroy;
 use nix::libc::waitpid;
 use nix::libc::{
@@ -26,60 +36,150 @@ use nix::libc::{
 };
 use termion::color;
 
+use crate::args::Command;
 use crate::collect::FileCollection;
-
-
+use crate::filter::{Filter, Matcher};
+use crate::golden::{self, GoldenOutcome};
+use crate::record_collection::{LogTypeMessage, ProcessExit, TestStatus};
+use crate::core_dump;
+use crate::coverage;
+use crate::memcheck;
+use crate::sanitizer;
+use scratch::{SCRATCH_ENV, STDERR_CAPTURE, STDOUT_CAPTURE, Scratch};
+
+// The runtime writes its frames to this descriptor,
+// leaving stdout to the program itself
+const PROTOCOL_FD: RawFd = 3;
+const PROTOCOL_FD_ENV: &str = "TESTRUNNER_PROTOCOL_FD";
+// Asks the runtime to only register its tests and exit
+const LIST_ENV: &str = "TESTRUNNER_LIST";
+// Asks the runtime to fork a process per test
+const ISOLATE_ENV: &str = "TESTRUNNER_ISOLATE";
+// Asks the runtime to run every test one at a time
+const SERIAL_ENV: &str = "TESTRUNNER_SERIAL";
+// Wait between two polls of the spawned executables,
+// still running ones are shown every few polls
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+const RUNNING_EVERY_ROUNDS: u32 = 50;
+// Time an executable has to send the hello frame while listing,
+// executables without runtime.h are stopped instead of running their tests
+const LIST_HELLO_DEADLINE: Duration = Duration::from_millis(500);
+// Program name reported by the runtime, argv[0] is the
+// executable itself when spawned through a wrapper
+const PROGRAM_ENV: &str = "TESTRUNNER_PROGRAM";
+// Folder gcov-instrumented children write their `.gcda` files below
+const GCOV_PREFIX_ENV: &str = "GCOV_PREFIX";
 
 #[derive(Debug)]
 enum SpawnErr {
     FailedToConvertCChar,
     SpawnChildFailed,
+    ScratchDirFailed,
 }
 
 #[derive(Debug)]
 enum ProcessErr {
-    FailedExit,
-    Crashed,
-    Stopped,
+    FailedExit(i32),
+    Crashed(i32),
+    CoreDumped(i32),
+    Stopped(i32),
     UndefinedTermination,
 }
 
-type PidsTrack = (Box<[libc::pid_t]>, Box<[usize]>);
+impl From<&ProcessErr> for ProcessExit {
+    fn from(value: &ProcessErr) -> Self {
+        match value {
+            ProcessErr::FailedExit(code) => ProcessExit::Exited(*code),
+            ProcessErr::Crashed(signal) | ProcessErr::CoreDumped(signal) => {
+                ProcessExit::Signaled(*signal)
+            }
+            ProcessErr::Stopped(signal) => ProcessExit::Stopped(*signal),
+            ProcessErr::UndefinedTermination => ProcessExit::Unknown,
+        }
+    }
+}
+
+type PidsTrack = (
+    Box<[libc::pid_t]>,
+    Box<[usize]>,
+    Box<[Option<Scratch>]>,
+    Box<[Instant]>,
+);
 
-fn init_pipe_with_file_action() -> (Vec<OwnedFd>, Vec<OwnedFd>, Vec<posix_spawn_file_actions_t>) {
+fn init_pipes() -> (Vec<OwnedFd>, Vec<OwnedFd>) {
     let default_pool_count = get_global_config_ref().process.max_child_spawn;
 
     let mut readfd_list = Vec::<OwnedFd>::with_capacity(default_pool_count);
     let mut writefd_list = Vec::<OwnedFd>::with_capacity(default_pool_count);
-    let mut action_files: Vec<posix_spawn_file_actions_t> = Vec::with_capacity(default_pool_count);
 
     for _ in 0..default_pool_count {
         let (readfd, writefd) = unistd::pipe().expect("Failed to create pipelines");
 
-        action_files.push(file_action_t_init(&readfd, &writefd));
-
         readfd_list.push(readfd);
         writefd_list.push(writefd);
     }
 
-    (readfd_list, writefd_list, action_files)
+    (readfd_list, writefd_list)
 }
 
-fn file_action_t_init(readfd: &OwnedFd, writefd: &OwnedFd) -> posix_spawn_file_actions_t {
+fn file_action_t_init(
+    readfd: RawFd,
+    writefd: RawFd,
+    cwd: &CString,
+    stdin: Option<&CString>,
+    stdout: Option<&CString>,
+    stderr: Option<&CString>,
+) -> posix_spawn_file_actions_t {
     let mut file_action: posix_spawn_file_actions_t = unsafe { std::mem::zeroed() };
 
-    use std::os::fd::AsRawFd;
-
+    use nix::libc::posix_spawn_file_actions_addchdir_np;
     use nix::libc::posix_spawn_file_actions_addclose;
+    use nix::libc::posix_spawn_file_actions_addopen;
 
     let raw_file_action: *mut _ = &mut file_action;
     unsafe {
         posix_spawn_file_actions_init(raw_file_action);
 
-        posix_spawn_file_actions_adddup2(raw_file_action, writefd.as_raw_fd(), libc::STDOUT_FILENO);
+        posix_spawn_file_actions_addclose(raw_file_action, readfd);
+
+        posix_spawn_file_actions_adddup2(raw_file_action, writefd, PROTOCOL_FD);
+        if writefd != PROTOCOL_FD {
+            posix_spawn_file_actions_addclose(raw_file_action, writefd);
+        }
+
+        if let Some(stdout) = stdout {
+            posix_spawn_file_actions_addopen(
+                raw_file_action,
+                libc::STDOUT_FILENO,
+                stdout.as_ptr(),
+                libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC,
+                0o644,
+            );
+        }
+
+        if let Some(stderr) = stderr {
+            posix_spawn_file_actions_addopen(
+                raw_file_action,
+                libc::STDERR_FILENO,
+                stderr.as_ptr(),
+                libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC,
+                0o644,
+            );
+        }
+
+        // opened before changing directory so the fixture path stays
+        // relative to the runner
+        if let Some(stdin) = stdin {
+            posix_spawn_file_actions_addopen(
+                raw_file_action,
+                libc::STDIN_FILENO,
+                stdin.as_ptr(),
+                libc::O_RDONLY,
+                0,
+            );
+        }
 
-        posix_spawn_file_actions_addclose(raw_file_action, writefd.as_raw_fd());
-        posix_spawn_file_actions_addclose(raw_file_action, readfd.as_raw_fd());
+        posix_spawn_file_actions_addchdir_np(raw_file_action, cwd.as_ptr());
     }
 
     file_action
@@ -91,26 +191,133 @@ use std::sync::Arc;
 fn spawn_process(
     pid: &mut libc::pid_t,
     exe_str: &str,
-    file_action: &posix_spawn_file_actions_t,
+    fds: (RawFd, RawFd),
+    options: &SpawnOptions,
+    scratch: &Scratch,
+    capture_stdout: bool,
+    capture_stderr: bool,
 ) -> Result<(), SpawnErr> {
     let pid_ref = pid as *mut _;
 
+    // argv[0] keeps the collected path since the runtime reports it back as the
+    // program name, but the child changes directory before exec so the file
+    // itself has to be located through an absolute path
     let exe = CString::new(exe_str).map_err(|_| SpawnErr::FailedToConvertCChar)?;
+    let exe_path = std::fs::canonicalize(exe_str)
+        .ok()
+        .and_then(|p| p.to_str().map(str::to_owned))
+        .ok_or(SpawnErr::FailedToConvertCChar)?;
+    let exe_path = CString::new(exe_path).map_err(|_| SpawnErr::FailedToConvertCChar)?;
+
+    let cwd = options
+        .cwd
+        .as_deref()
+        .map(str::to_owned)
+        .or_else(|| scratch.path().to_str().map(str::to_owned))
+        .ok_or(SpawnErr::FailedToConvertCChar)?;
+    let cwd = CString::new(cwd).map_err(|_| SpawnErr::FailedToConvertCChar)?;
+
+    let stdin = options
+        .stdin
+        .as_deref()
+        .map(CString::new)
+        .transpose()
+        .map_err(|_| SpawnErr::FailedToConvertCChar)?;
+
+    let stdout = capture_stdout
+        .then(|| scratch.path().join(STDOUT_CAPTURE))
+        .map(|p| CString::new(p.as_os_str().as_encoded_bytes()))
+        .transpose()
+        .map_err(|_| SpawnErr::FailedToConvertCChar)?;
+
+    let stderr = capture_stderr
+        .then(|| scratch.path().join(STDERR_CAPTURE))
+        .map(|p| CString::new(p.as_os_str().as_encoded_bytes()))
+        .transpose()
+        .map_err(|_| SpawnErr::FailedToConvertCChar)?;
+
+    let mut file_action = file_action_t_init(
+        fds.0,
+        fds.1,
+        &cwd,
+        stdin.as_ref(),
+        stdout.as_ref(),
+        stderr.as_ref(),
+    );
 
-    let file_action = file_action as *const _;
+    let args = options
+        .args
+        .iter()
+        .map(|arg| CString::new(arg.as_str()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| SpawnErr::FailedToConvertCChar)?;
+
+    let envs = options
+        .env
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .chain(std::iter::once((SCRATCH_ENV, scratch.path().to_str().unwrap_or(""))))
+        .chain(std::iter::once((PROGRAM_ENV, exe_str)))
+        .map(|(key, value)| CString::new(format!("{}={}", key, value)))
+        .chain(std::iter::once(CString::new(format!(
+            "{}={}",
+            PROTOCOL_FD_ENV, PROTOCOL_FD
+        ))))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| SpawnErr::FailedToConvertCChar)?;
+
+    let wrapper = wrapper_command(exe_str, scratch)
+        .into_iter()
+        .map(CString::new)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| SpawnErr::FailedToConvertCChar)?;
+
+    // wrapped executables are handed to the wrapper by their absolute path
+    let program: Vec<*const libc::c_char> = if wrapper.is_empty() {
+        vec![exe.as_ptr()]
+    } else {
+        wrapper
+            .iter()
+            .map(|arg| arg.as_ptr())
+            .chain(std::iter::once(exe_path.as_ptr()))
+            .collect()
+    };
 
-    let argv = [exe.as_ptr(), std::ptr::null()];
+    let argv: Vec<*const libc::c_char> = program
+        .into_iter()
+        .chain(args.iter().map(|arg| arg.as_ptr()))
+        .chain(std::iter::once(std::ptr::null()))
+        .collect();
+
+    let envp: Vec<*const libc::c_char> = envs
+        .iter()
+        .map(|env| env.as_ptr())
+        .chain(std::iter::once(std::ptr::null()))
+        .collect();
 
     let ret;
     unsafe {
-        ret = posix_spawn(
-            pid_ref,
-            exe.as_ptr(),
-            file_action,
-            std::ptr::null(),
-            argv.as_ptr() as *mut _,
-            std::ptr::null(),
-        );
+        ret = match wrapper.first() {
+            // the wrapper is looked up in PATH
+            Some(program) => posix_spawnp(
+                pid_ref,
+                program.as_ptr(),
+                &file_action as *const _,
+                std::ptr::null(),
+                argv.as_ptr() as *const _,
+                envp.as_ptr() as *const _,
+            ),
+            None => posix_spawn(
+                pid_ref,
+                exe_path.as_ptr(),
+                &file_action as *const _,
+                std::ptr::null(),
+                argv.as_ptr() as *const _,
+                envp.as_ptr() as *const _,
+            ),
+        };
+
+        posix_spawn_file_actions_destroy(&mut file_action as *mut _);
     }
 
     if ret != 0 {
@@ -120,15 +327,18 @@ fn spawn_process(
     Ok(())
 }
 
-fn read_pid_status(pid: &libc::pid_t, origin: &str) -> Result<bool, ProcessErr> {
+fn read_pid_status(pid: &libc::pid_t, origin: &str, show_running: bool) -> Result<bool, ProcessErr> {
     let mut status: libc::c_int = 0;
 
     let pid_r = unsafe { waitpid(*pid, &mut status as *mut _, WNOHANG) };
 
     if pid_r == 0 {
         //process still runing
+        if !show_running {
+            return Ok(false);
+        }
 
-        println!(
+        progress!(
             "{}[ Running... ] {}{}",
             termion::color::Fg(color::Rgb(255, 195, 51)),
             origin,
@@ -142,7 +352,7 @@ fn read_pid_status(pid: &libc::pid_t, origin: &str) -> Result<bool, ProcessErr>
 
         let exit_stat = WEXITSTATUS(status);
         if exit_stat == 0 {
-            println!(
+            progress!(
                 "{}[ Finished Executing - {} ]{}",
                 termion::color::Fg(color::Green),
                 origin,
@@ -151,89 +361,433 @@ fn read_pid_status(pid: &libc::pid_t, origin: &str) -> Result<bool, ProcessErr>
 
             return Ok(true);
         } else {
-            println!(
+            progress!(
                 "{}[ Failed - {} ]{}",
                 termion::color::Fg(color::Red),
                 origin,
                 termion::color::Fg(color::Reset)
             );
 
-            return Err(ProcessErr::FailedExit);
+            return Err(ProcessErr::FailedExit(exit_stat));
         }
     } else if WIFSIGNALED(status) {
         // Process crashed from segfault
 
         let signal = WTERMSIG(status);
-        println!(
-            "{}[ Process Crashed: Origin:{}, Signal:{} ]{}",
+        let core_dumped = WCOREDUMP(status);
+        progress!(
+            "{}[ Process Crashed: Origin:{}, Signal:{}{} ]{}",
             termion::color::Fg(color::Red),
             origin,
             signal,
+            if core_dumped { " (core dumped)" } else { "" },
             termion::color::Fg(color::Reset)
         );
-        return Err(ProcessErr::Crashed);
+        if core_dumped {
+            return Err(ProcessErr::CoreDumped(signal));
+        }
+        return Err(ProcessErr::Crashed(signal));
     } else if WIFSTOPPED(status) {
         let signal = WSTOPSIG(status);
-        println!(
+        progress!(
             "{}[ Process Stopped: Origin:{}, Signal:{} ]{}",
             termion::color::Fg(color::Red),
             origin,
             signal,
             termion::color::Fg(color::Reset)
         );
-        return Err(ProcessErr::Stopped);
+        return Err(ProcessErr::Stopped(signal));
     }
 
     Err(ProcessErr::UndefinedTermination)
 }
 
+// Kills a running executable and reaps it
+fn stop_process(pid: libc::pid_t) {
+    let mut status: libc::c_int = 0;
+    unsafe {
+        libc::kill(pid, libc::SIGKILL);
+        waitpid(pid, &mut status as *mut _, 0);
+    }
+}
+
+// Returns false when the executable could not be started, it is then
+// recorded as a finished program that never ran
 fn fill_spawn_pool(
-    pid: &mut libc::pid_t,
-    pid_index_ref: &mut usize,
+    pids: &mut PidsTrack,
+    slot: usize,
     file_details: (usize, &(String, String)),
-    file_action: &posix_spawn_file_actions_t,
+    test_names: Option<&Vec<String>>,
+    fds: (RawFd, RawFd),
     shared_collection: &mut TestRecord,
-) {
+) -> bool {
     //spawn new process
-    let stat = spawn_process(pid, file_details.1.1.as_str(), &file_action);
+    let config = get_global_config_ref();
+    let mut options = config.spawn.resolve(file_details.1.1.as_str());
+    let filter = test_filter(test_names);
+    if !filter.accepts(EXIT_CODE_TEST_NAME) {
+        // a runtime.h binary whose tests were all filtered out registers
+        // nothing, its exit code alone says nothing about the tests asked for
+        let _ = shared_collection.skip_exit_code_test(file_details.1.1.trim());
+    }
+    options
+        .env
+        .extend(filter.to_env().into_iter().map(|(k, v)| (k.to_owned(), v)));
+    if get_global_args_ref().command == Command::List {
+        options.env.insert(LIST_ENV.to_owned(), "1".to_owned());
+    }
+    if options.isolate {
+        options.env.insert(ISOLATE_ENV.to_owned(), "1".to_owned());
+    }
+    if options.serial {
+        options.env.insert(SERIAL_ENV.to_owned(), "1".to_owned());
+    }
+    if config.coverage.enabled {
+        let prefix = coverage::gcov_prefix(file_details.1.1.as_str());
+        options
+            .env
+            .insert(GCOV_PREFIX_ENV.to_owned(), prefix.to_string_lossy().into_owned());
+    }
+    let sanitized = is_sanitized();
+    if sanitized {
+        for (key, value) in sanitizer::sanitizer_env(&config.sanitizer) {
+            options.env.entry(key).or_insert(value);
+        }
+    }
+    let golden = is_golden(file_details.1.1.as_str());
+    // executables without runtime.h run for real while listing,
+    // their output must not end up in the listing
+    let listing = get_global_args_ref().command == Command::List;
+
+    // registered before spawning, the child's first frames can
+    // reach the pipeline reader before posix_spawn returns
+    let _ = shared_collection.register_process(file_details.1.1.trim().to_owned());
+
+    let stat = Scratch::create(file_details.0, &file_details.1.0)
+        .map_err(|_| SpawnErr::ScratchDirFailed)
+        .and_then(|scratch| {
+            spawn_process(
+                &mut pids.0[slot],
+                file_details.1.1.as_str(),
+                fds,
+                &options,
+                &scratch,
+                golden || listing,
+                sanitized || listing,
+            )?;
+            Ok(scratch)
+        });
 
     match stat {
-        Ok(_) => {
-            println!(
+        Ok(scratch) => {
+            progress!(
                 "{}[ Executing: {} ]{}",
                 color::Fg(color::Rgb(255, 195, 51)),
                 file_details.1.0,
                 color::Fg(color::Reset)
             );
-            *pid_index_ref = file_details.0;
-
-            let _ = shared_collection.register_process(file_details.1.1.trim().to_owned());
+            pids.1[slot] = file_details.0;
+            pids.2[slot] = Some(scratch);
+            pids.3[slot] = Instant::now();
+            true
         }
         Err(e) => {
-            println!(
+            progress!(
                 "{}[ Set-up Failed: {} [{:?}]]{}",
                 color::Fg(color::Magenta),
                 file_details.0,
                 e,
                 color::Fg(color::Reset)
             );
+            pids.0[slot] = -1;
+            let _ = shared_collection.record_exit(
+                file_details.1.1.trim(),
+                ProcessExit::NotStarted,
+                Duration::ZERO,
+            );
+            false
+        }
+    }
+}
+
+// Test filter of the command line, with the include patterns replaced by
+// the exact names when only some tests of the executable should start
+fn test_filter(test_names: Option<&Vec<String>>) -> Filter {
+    let mut filter = get_global_args_ref().test_filter.clone();
+
+    if let Some(names) = test_names {
+        filter.include = names
+            .iter()
+            .filter_map(|n| PathPattern::new(n).ok().map(Matcher::Glob))
+            .collect();
+    }
+
+    filter
+}
+
+// Wrapper command of an executable, empty when run directly
+fn wrapper_command(exe_path: &str, scratch: &Scratch) -> Vec<String> {
+    let config = get_global_config_ref();
+    if get_global_args_ref().command == Command::List {
+        return Vec::new();
+    }
+
+    let mut command = Vec::new();
+    if config.core_dump.enabled {
+        command.extend(core_dump::core_limit_command());
+    }
+
+    if let Some(wrapper) = config.wrapper.as_ref()
+        && wrapper.wraps(exe_path)
+    {
+        command.extend(wrapper.command.iter().cloned());
+        if wrapper.memcheck_xml {
+            let xml_file = scratch.path().join(memcheck::MEMCHECK_XML);
+            command.extend(memcheck::xml_args(&xml_file.to_string_lossy()));
         }
     }
+
+    command
+}
+
+#[inline]
+fn is_sanitized() -> bool {
+    get_global_args_ref().command != Command::List && get_global_config_ref().sanitizer.is_enabled()
+}
+
+// Checks done on the leftovers of a finished executable,
+// returns false when one of them failed
+fn inspect_run(
+    fc: &FileCollection,
+    exe_index: usize,
+    exited_ok: bool,
+    scratch: &Scratch,
+    shared_collection: &TestRecord,
+) -> bool {
+    if get_global_args_ref().command == Command::List {
+        return true;
+    }
+
+    let golden = check_golden(fc, exe_index, exited_ok, scratch, shared_collection);
+    let sanitizer = check_sanitizer(fc, exe_index, scratch, shared_collection);
+    let memcheck = check_memcheck(fc, exe_index, scratch, shared_collection);
+
+    golden && sanitizer && memcheck
+}
+
+// Backtrace of a crashed executable, read from the core
+// left in its working directory
+fn check_core_dump(
+    fc: &FileCollection,
+    exe_index: usize,
+    pid: libc::pid_t,
+    signal: i32,
+    scratch: &Scratch,
+    shared_collection: &TestRecord,
+) {
+    let config = &get_global_config_ref().core_dump;
+    if !config.enabled {
+        return;
+    }
+
+    let exe_path = fc.str_file_name_from(exe_index);
+    let cwd = get_global_config_ref()
+        .spawn
+        .resolve(exe_path)
+        .cwd
+        .map(PathBuf::from)
+        .unwrap_or_else(|| scratch.path().to_path_buf());
+
+    let Some(core) = core_dump::find_core(&cwd, pid) else {
+        progress!(
+            "{}[ No core found in {} for {} ]{}",
+            color::Fg(color::Yellow),
+            cwd.display(),
+            exe_path,
+            color::Fg(color::Reset)
+        );
+        return;
+    };
+
+    let diagnostic = core_dump::inspect_core(config, exe_path, pid, &core, signal);
+    progress!(
+        "{}[ {} - {} ]{}",
+        color::Fg(color::Red),
+        diagnostic.message,
+        exe_path,
+        color::Fg(color::Reset)
+    );
+
+    let _ = shared_collection.record_diagnostics(exe_path, vec![diagnostic]);
+}
+
+fn check_memcheck(
+    fc: &FileCollection,
+    exe_index: usize,
+    scratch: &Scratch,
+    shared_collection: &TestRecord,
+) -> bool {
+    let exe_path = fc.str_file_name_from(exe_index);
+    let Some(wrapper) = get_global_config_ref().wrapper.as_ref() else {
+        return true;
+    };
+    if !wrapper.memcheck_xml || !wrapper.wraps(exe_path) {
+        return true;
+    }
+
+    // a missing or broken report fails the program instead of passing as clean
+    let xml_file = scratch.path().join(memcheck::MEMCHECK_XML);
+    let diagnostics = std::fs::read_to_string(&xml_file)
+        .map_err(|e| format!("Unable to read {}: {}", xml_file.display(), e))
+        .and_then(|xml| {
+            memcheck::parse_memcheck(&xml, wrapper.frames)
+                .map_err(|e| format!("Unable to parse {}: {}", xml_file.display(), e))
+        })
+        .unwrap_or_else(|reason| vec![memcheck::unreadable_report(reason)]);
+
+    for diagnostic in diagnostics.iter() {
+        progress!(
+            "{}[ {}: {} - {} ]{}",
+            color::Fg(color::Red),
+            diagnostic.tool,
+            diagnostic.kind,
+            exe_path,
+            color::Fg(color::Reset)
+        );
+    }
+
+    let clean = diagnostics.is_empty();
+    let _ = shared_collection.record_diagnostics(exe_path, diagnostics);
+
+    clean
+}
+
+fn check_sanitizer(
+    fc: &FileCollection,
+    exe_index: usize,
+    scratch: &Scratch,
+    shared_collection: &TestRecord,
+) -> bool {
+    if !is_sanitized() {
+        return true;
+    }
+
+    let exe_path = fc.str_file_name_from(exe_index);
+    let Ok(stderr) = std::fs::read_to_string(scratch.path().join(STDERR_CAPTURE)) else {
+        return true;
+    };
+
+    let config = &get_global_config_ref().sanitizer;
+    let diagnostics = sanitizer::parse_reports(&stderr, config.frames);
+    for diagnostic in diagnostics.iter() {
+        progress!(
+            "{}[ {}: {} - {} ]{}",
+            color::Fg(color::Red),
+            diagnostic.tool,
+            diagnostic.kind,
+            exe_path,
+            color::Fg(color::Reset)
+        );
+    }
+
+    let clean = diagnostics.is_empty();
+    let _ = shared_collection.record_diagnostics(exe_path, diagnostics);
+
+    clean
+}
+
+#[inline]
+fn is_golden(exe_path: &str) -> bool {
+    get_global_args_ref().command != Command::List
+        && get_global_config_ref().golden.matches(exe_path)
+}
+
+// Compares the captured output of golden executables and records the
+// result as a test of the program, returns false on a mismatch
+fn check_golden(
+    fc: &FileCollection,
+    exe_index: usize,
+    exited_ok: bool,
+    scratch: &Scratch,
+    shared_collection: &TestRecord,
+) -> bool {
+    let exe_path = fc.str_file_name_from(exe_index);
+    if !is_golden(exe_path) {
+        return true;
+    }
+
+    let outcome = golden::compare_output(
+        exe_path,
+        &scratch.path().join(STDOUT_CAPTURE),
+        get_global_args_ref().bless,
+        exited_ok,
+    );
+
+    let (status, logs) = match outcome {
+        Ok(GoldenOutcome::Matched) => (TestStatus::Success, vec![]),
+        Ok(GoldenOutcome::Blessed) => (
+            TestStatus::Success,
+            vec![LogTypeMessage::Info(format!(
+                "Blessed {}",
+                golden::expected_path(exe_path)
+            ))],
+        ),
+        Ok(GoldenOutcome::MissingExpected) => (
+            TestStatus::Fail,
+            vec![LogTypeMessage::Warning(format!(
+                "Missing {}, run with --bless to create it",
+                golden::expected_path(exe_path)
+            ))],
+        ),
+        Ok(GoldenOutcome::Mismatch(diff)) => {
+            progress!(
+                "{}[ Output Mismatch: {} ]{}\n{}",
+                color::Fg(color::Red),
+                exe_path,
+                color::Fg(color::Reset),
+                diff
+            );
+            (TestStatus::Fail, vec![LogTypeMessage::Diff(diff)])
+        }
+        Err(e) => (
+            TestStatus::Fail,
+            vec![LogTypeMessage::Warning(format!(
+                "Unable to compare output: {}",
+                e
+            ))],
+        ),
+    };
+
+    let passed = status.passed();
+    let _ = shared_collection.insert_test(exe_path, golden::GOLDEN_TEST_NAME, status, logs);
+
+    passed
 }
 
 pub fn spawn_executable(fc: FileCollection) -> Option<CompiledRecord> {
-    let pool_limit = get_global_config_ref().process.max_child_spawn;
+    let process = &get_global_config_ref().process;
+    let pool_limit = process.max_child_spawn;
+
+    if get_global_config_ref().core_dump.enabled
+        && let Some(issue) = core_dump::core_pattern_issue()
+    {
+        eprintln!("Core dumps may not be found: {}", issue);
+    }
 
     let mut test_collection = TestRecord::new();
 
-    let (readfd_list, writefd_list, mut file_actions) = init_pipe_with_file_action();
+    let (readfd_list, writefd_list) = init_pipes();
+    let readfd_raw: Vec<RawFd> = readfd_list.iter().map(AsRawFd::as_raw_fd).collect();
 
     let mut pids: PidsTrack = (
         vec![-1; pool_limit].into_boxed_slice(),
         vec![0; pool_limit].into_boxed_slice(),
+        (0..pool_limit).map(|_| None).collect(),
+        vec![Instant::now(); pool_limit].into_boxed_slice(),
     );
 
-    let mut file_iter = fc.exe_info.iter().enumerate();
+    let mut pending: VecDeque<(usize, &(String, String))> =
+        fc.exe_info.iter().enumerate().collect();
 
     let flag = Arc::new(AtomicBool::new(true));
     // let reports = Arc::new(TRecord::new());
@@ -246,32 +800,54 @@ pub fn spawn_executable(fc: FileCollection) -> Option<CompiledRecord> {
     });
 
     let mut executable_left = fc.len();
+    let mut round: u32 = 0;
     while executable_left > 0 {
-        for i in 0..pool_limit {
+        for (i, writefd) in writefd_list.iter().enumerate() {
             if pids.0[i] != -1 {
                 continue;
             }
-            let Some(file_detials) = file_iter.next() else {
+
+            // executables over their group's cap wait for a later round
+            let running: Vec<&str> = (0..pool_limit)
+                .filter(|slot| pids.0[*slot] != -1)
+                .map(|slot| fc.str_file_name_from(pids.1[slot]))
+                .collect();
+            let Some(next) = pending
+                .iter()
+                .position(|(_, details)| process.admits(&details.1, &running))
+            else {
+                break;
+            };
+            let Some(file_detials) = pending.remove(next) else {
                 break;
             };
 
-            fill_spawn_pool(
-                &mut pids.0[i],
-                &mut pids.1[i],
+            let spawned = fill_spawn_pool(
+                &mut pids,
+                i,
                 file_detials,
-                &file_actions[i],
+                fc.test_names.get(&file_detials.1.1),
+                (readfd_raw[i], writefd.as_raw_fd()),
                 &mut test_collection,
             );
+            if !spawned {
+                executable_left = executable_left.saturating_sub(1);
+            }
         }
 
+        // short rounds keep the recorded wall time of a program close
+        // to its exit, running programs are only shown every few rounds
+        sleep(POLL_INTERVAL);
+        round = round.wrapping_add(1);
+        let show_running = round.is_multiple_of(RUNNING_EVERY_ROUNDS);
+
         for i in 0..pool_limit {
             if pids.0[i] == -1 {
                 continue;
             }
 
             //Get proccesses progress
-            sleep(Duration::from_millis(500));
-            let stat = { read_pid_status(&pids.0[i], fc.str_file_name_from(pids.1[i])) };
+            let stat = { read_pid_status(&pids.0[i], fc.str_file_name_from(pids.1[i]), show_running) };
 
             match stat {
                 Ok(res) => {
@@ -279,14 +855,54 @@ pub fn spawn_executable(fc: FileCollection) -> Option<CompiledRecord> {
                         //process finished
                         //store data that program finished
                         pids.0[i] = -1;
+                        let _ = test_collection.record_exit(
+                            fc.str_file_name_from(pids.1[i]),
+                            ProcessExit::Exited(0),
+                            pids.3[i].elapsed(),
+                        );
+                        if let Some(scratch) = pids.2[i].take() {
+                            let passed = inspect_run(&fc, pids.1[i], true, &scratch, &test_collection);
+                            let tests_failed = test_collection
+                                .program_failed(fc.str_file_name_from(pids.1[i]));
+                            scratch.finish(!passed || tests_failed);
+                        }
+                    } else if listing()
+                        && pids.3[i].elapsed() > LIST_HELLO_DEADLINE
+                        && !test_collection.sent_hello(fc.str_file_name_from(pids.1[i]))
+                    {
+                        // listed with its exit code test, without running it to the end
+                        let elapsed = pids.3[i].elapsed();
+                        stop_process(pids.0[i]);
+                        pids.0[i] = -1;
+                        let _ = test_collection.record_exit(
+                            fc.str_file_name_from(pids.1[i]),
+                            ProcessExit::Signaled(libc::SIGKILL),
+                            elapsed,
+                        );
+                        if let Some(scratch) = pids.2[i].take() {
+                            scratch.finish(false);
+                        }
                     } else {
                         //process still running
                         continue;
                     }
                 }
-                Err(_) => {
+                Err(e) => {
                     // store in data that program failed
+                    let pid = pids.0[i];
                     pids.0[i] = -1;
+                    let _ = test_collection.record_exit(
+                        fc.str_file_name_from(pids.1[i]),
+                        ProcessExit::from(&e),
+                        pids.3[i].elapsed(),
+                    );
+                    if let Some(scratch) = pids.2[i].take() {
+                        inspect_run(&fc, pids.1[i], false, &scratch, &test_collection);
+                        if let ProcessErr::CoreDumped(signal) = e {
+                            check_core_dump(&fc, pids.1[i], pid, signal, &scratch, &test_collection);
+                        }
+                        scratch.finish(true);
+                    }
                 }
             }
 
@@ -294,11 +910,7 @@ pub fn spawn_executable(fc: FileCollection) -> Option<CompiledRecord> {
         }
     }
 
-    unsafe {
-        while let Some(mut fa) = file_actions.pop() {
-            posix_spawn_file_actions_destroy(&mut fa as *mut _);
-        }
-    }
+    scratch::remove_run_root();
 
     drop(writefd_list);
     flag.store(false, Ordering::Relaxed);
@@ -312,7 +924,6 @@ pub fn spawn_executable(fc: FileCollection) -> Option<CompiledRecord> {
 
 mod pipe_handler {
     use std::{
-        fmt::Display,
         fs::File,
         io::Read,
         marker::PhantomData,
@@ -324,7 +935,6 @@ mod pipe_handler {
         },
     };
 
-    use termion::color;
     use threadpool::ThreadPool;
 
     use crate::{
@@ -341,8 +951,8 @@ mod pipe_handler {
         readfds: Vec<OwnedFd>,
         flag: Arc<AtomicBool>,
     ){
-        println!("[ ThreadRunner is Listening ]");
-        println!("[ Pipeline Reader Active ]");
+        progress!("[ ThreadRunner is Listening ]");
+        progress!("[ Pipeline Reader Active ]");
 
         // Set all file descrptors as File Objects
         let readfds = set_fd_to_file(readfds);
@@ -360,13 +970,13 @@ mod pipe_handler {
                 let payload = record_collection::bin_convert(&bin_buff);
                 // send job to threadpool
                 if tx.send(payload).is_err() {
-                    println!("Unable to send Thread Jobs!");
+                    progress!("Unable to send Thread Jobs!");
                     break;
                 }
             }
         }
 
-        println!("[ Draining remaining pipe content ]");
+        progress!("[ Draining remaining pipe content ]");
 
         let mut clean = true;
         while clean {
@@ -383,18 +993,16 @@ mod pipe_handler {
 
                 // send job to threadpool
                 if tx.send(payload).is_err() {
-                    println!("Unable to send Thread Jobs!");
+                    progress!("Unable to send Thread Jobs!");
                     break;
                 }
             }
         }
 
-        println!("[ Closing Pipeline Reader ]");
+        progress!("[ Closing Pipeline Reader ]");
 
         drop(tx);
         threadpool.join();
-
-        ()
     }
 
     fn set_fd_to_file(readfds: Vec<OwnedFd>) -> Vec<File> {
@@ -454,8 +1062,128 @@ mod pipe_handler {
             }
         }
     }
+}
+
+mod scratch {
+    use std::{
+        fs, io,
+        path::{Path, PathBuf},
+    };
+
+    use termion::color;
+
+    use crate::get_global_config_ref;
+
+    /// Environment variable holding the scratch directory of a test binary
+    pub const SCRATCH_ENV: &str = "TESTRUNNER_SCRATCH_DIR";
+    /// File inside the scratch directory receiving captured stdout
+    pub const STDOUT_CAPTURE: &str = "stdout.txt";
+    /// File inside the scratch directory receiving captured stderr
+    pub const STDERR_CAPTURE: &str = "stderr.txt";
+
+    /// Fresh directory handed to a single spawned executable,
+    /// removed when dropped unless kept for inspection
+    #[derive(Debug)]
+    pub struct Scratch {
+        path: PathBuf,
+        kept: bool,
+    }
+
+    fn run_root() -> PathBuf {
+        let root = get_global_config_ref()
+            .spawn
+            .scratch_root
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir);
+        let root = root.join(format!("testrunner-{}", std::process::id()));
+
+        // absolute since children run inside their scratch directory
+        std::path::absolute(&root).unwrap_or(root)
+    }
+
+    impl Scratch {
+        pub fn create(index: usize, file_name: &str) -> io::Result<Self> {
+            Self::create_in(&run_root(), index, file_name)
+        }
+
+        fn create_in(root: &Path, index: usize, file_name: &str) -> io::Result<Self> {
+            let path = root.join(format!("{}-{}", index, file_name));
+
+            if path.exists() {
+                fs::remove_dir_all(&path)?;
+            }
+            fs::create_dir_all(&path)?;
+
+            Ok(Self { path, kept: false })
+        }
+
+        #[inline]
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+
+        pub fn finish(self, failed: bool) {
+            self.finish_with(failed && get_global_config_ref().spawn.keep_failed_scratch);
+        }
+
+        fn finish_with(mut self, keep: bool) {
+            if keep {
+                progress!(
+                    "{}[ Kept scratch directory: {} ]{}",
+                    color::Fg(color::Yellow),
+                    self.path.display(),
+                    color::Fg(color::Reset)
+                );
+                self.kept = true;
+            }
+        }
+    }
 
-    trait ReportDisplay {}
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            if !self.kept {
+                let _ = fs::remove_dir_all(&self.path);
+            }
+        }
+    }
 
-    impl ReportDisplay for () {}
+    // Only removes the run directory when no scratch directory was kept
+    pub fn remove_run_root() {
+        let _ = fs::remove_dir(run_root());
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn scratch_is_removed_unless_kept() {
+            let root = std::env::temp_dir().join(format!("scratch-test-{}", std::process::id()));
+
+            let dropped = Scratch::create_in(&root, 0, "dropped").unwrap();
+            let dropped_path = dropped.path().to_path_buf();
+            fs::write(dropped_path.join("out.txt"), "out").unwrap();
+            drop(dropped);
+            assert!(!dropped_path.exists());
+
+            let passed = Scratch::create_in(&root, 1, "passed").unwrap();
+            let passed_path = passed.path().to_path_buf();
+            passed.finish_with(false);
+            assert!(!passed_path.exists());
+
+            let kept = Scratch::create_in(&root, 2, "kept").unwrap();
+            let kept_path = kept.path().to_path_buf();
+            fs::write(kept_path.join("out.txt"), "out").unwrap();
+            kept.finish_with(true);
+            assert!(kept_path.join("out.txt").exists());
+
+            // a new run of the same slot starts from an empty directory
+            let again = Scratch::create_in(&root, 2, "kept").unwrap();
+            assert!(!again.path().join("out.txt").exists());
+            drop(again);
+
+            fs::remove_dir_all(&root).unwrap();
+        }
+    }
 }
@@ -7,3 +7,14 @@ pub fn bytes_to_trimmed_string(bytes: &[u8]) -> Result<String, ()> {
         .map_err(|_| ())
         .map(|s| s.to_string())
 }
+
+/// Writes to the given file, or stdout when no file is given
+pub fn write_output(path: Option<&str>, content: &str) -> std::io::Result<()> {
+    match path {
+        Some(path) => std::fs::write(path, content),
+        None => {
+            print!("{}", content);
+            Ok(())
+        }
+    }
+}
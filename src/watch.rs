@@ -0,0 +1,226 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use nix::{
+    errno::Errno,
+    sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor},
+};
+use termion::color;
+use walkdir::WalkDir;
+
+use crate::{
+    collect::{self, FileCollection},
+    finish_run, get_global_args_ref, get_global_config_ref,
+    record_collection::collection::CompiledRecord,
+    retry, save_report,
+};
+
+// Builds write several events per binary, rerun once they settled
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// What an inotify event of a watched folder asks for
+#[derive(Debug, PartialEq)]
+enum WatchEvent {
+    NewFolder,
+    Written,
+    Ignored,
+}
+
+// New files are only rerun once written and closed
+fn classify(mask: AddWatchFlags) -> WatchEvent {
+    if mask.contains(AddWatchFlags::IN_ISDIR) {
+        WatchEvent::NewFolder
+    } else if mask.contains(AddWatchFlags::IN_CREATE) {
+        WatchEvent::Ignored
+    } else {
+        WatchEvent::Written
+    }
+}
+
+// Keeps the executables of the collection among the changed paths
+fn changed_executables(file_collection: &mut FileCollection, changed: BTreeSet<PathBuf>) {
+    let changed: BTreeSet<String> = changed
+        .into_iter()
+        .filter_map(|path| path.to_str().map(str::to_owned))
+        .collect();
+
+    file_collection.retain(|path| changed.contains(path));
+}
+
+struct Watcher {
+    inotify: Inotify,
+    folders: HashMap<WatchDescriptor, PathBuf>,
+}
+
+impl Watcher {
+    fn new(root: &str) -> nix::Result<Self> {
+        let mut watcher = Watcher {
+            inotify: Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?,
+            folders: HashMap::new(),
+        };
+        watcher.watch_tree(Path::new(root))?;
+
+        Ok(watcher)
+    }
+
+    // Watches `root` and every folder below it
+    fn watch_tree(&mut self, root: &Path) -> nix::Result<()> {
+        let flags =
+            AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_MOVED_TO | AddWatchFlags::IN_CREATE;
+
+        for entry in WalkDir::new(root).into_iter().flatten() {
+            if entry.file_type().is_dir() {
+                let wd = self.inotify.add_watch(entry.path(), flags)?;
+                self.folders.insert(wd, entry.path().to_path_buf());
+            }
+        }
+
+        Ok(())
+    }
+
+    // Paths written since the last call, new folders are watched as well
+    fn changed_paths(&mut self) -> nix::Result<Vec<PathBuf>> {
+        let events = match self.inotify.read_events() {
+            Ok(events) => events,
+            Err(Errno::EAGAIN) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut changed = Vec::new();
+        for event in events {
+            let (Some(folder), Some(name)) = (self.folders.get(&event.wd), event.name) else {
+                continue;
+            };
+            let path = folder.join(name);
+
+            match classify(event.mask) {
+                WatchEvent::NewFolder => self.watch_tree(&path)?,
+                WatchEvent::Written => changed.push(path),
+                WatchEvent::Ignored => {}
+            }
+        }
+
+        Ok(changed)
+    }
+}
+
+/// Reruns the executables rebuilt in `bin_target` until interrupted,
+/// `record` holds the results of every executable for the report
+pub fn watch_bin_target(mut record: CompiledRecord) {
+    let target = get_global_config_ref().target_config.bin_target.as_str();
+    let mut watcher = Watcher::new(target).unwrap_or_else(|e| {
+        eprintln!("Unable to watch {}: {}", target, e);
+        std::process::exit(1);
+    });
+
+    let mut pending = BTreeSet::new();
+    let mut last_change = Instant::now();
+
+    loop {
+        println!(
+            "{}[ Watching {} for rebuilt executables... ]{}",
+            color::Fg(color::Yellow),
+            target,
+            color::Fg(color::Reset)
+        );
+
+        loop {
+            let changed = watcher.changed_paths().unwrap_or_else(|e| {
+                eprintln!("Unable to watch {}: {}", target, e);
+                std::process::exit(1);
+            });
+
+            if !changed.is_empty() {
+                pending.extend(changed);
+                last_change = Instant::now();
+            } else if !pending.is_empty() && last_change.elapsed() >= SETTLE_TIME {
+                break;
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        let Ok(mut file_collection) =
+            collect::collect_test_files(target, &get_global_args_ref().exe_filter)
+        else {
+            continue;
+        };
+        changed_executables(&mut file_collection, std::mem::take(&mut pending));
+        if file_collection.len() == 0 {
+            continue;
+        }
+
+        println!(
+            "{}[ Rerunning {} changed executables ]{}",
+            color::Fg(color::Yellow),
+            file_collection.len(),
+            color::Fg(color::Reset)
+        );
+
+        if let Some(mut run) = retry::run_with_retries(file_collection) {
+            finish_run(&mut run);
+            record.replace_programs(run);
+            save_report(&record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    use super::*;
+    use crate::filter::Filter;
+
+    #[test]
+    fn only_written_files_are_rerun() {
+        assert_eq!(classify(AddWatchFlags::IN_CLOSE_WRITE), WatchEvent::Written);
+        assert_eq!(classify(AddWatchFlags::IN_MOVED_TO), WatchEvent::Written);
+        assert_eq!(classify(AddWatchFlags::IN_CREATE), WatchEvent::Ignored);
+        assert_eq!(
+            classify(AddWatchFlags::IN_CREATE | AddWatchFlags::IN_ISDIR),
+            WatchEvent::NewFolder
+        );
+        assert_eq!(
+            classify(AddWatchFlags::IN_MOVED_TO | AddWatchFlags::IN_ISDIR),
+            WatchEvent::NewFolder
+        );
+    }
+
+    #[test]
+    fn changed_paths_select_their_executables() {
+        let root = std::env::temp_dir().join(format!("watch-test-{}", std::process::id()));
+        fs::create_dir_all(root.join("sub")).unwrap();
+        for exe in ["a", "b", "sub/c"] {
+            fs::write(root.join(exe), "").unwrap();
+            fs::set_permissions(root.join(exe), fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        fs::write(root.join("a.flags"), "").unwrap();
+
+        let mut file_collection = collect::collect_test_files(&root, &Filter::default()).unwrap();
+        changed_executables(
+            &mut file_collection,
+            BTreeSet::from([
+                root.join("a"),
+                root.join("a.flags"),
+                root.join("sub/c"),
+                root.join("gone"),
+            ]),
+        );
+
+        let mut rerun: Vec<&str> = file_collection
+            .exe_info
+            .iter()
+            .map(|e| e.0.as_str())
+            .collect();
+        rerun.sort();
+        assert_eq!(rerun, ["a", "c"]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}